@@ -1,5 +1,8 @@
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::parser::parse_duration;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -18,40 +21,212 @@ pub enum Commands {
     },
     /// Discover and parse all SQL files in a directory
     Discover {
-        /// Directory containing SQL model files
-        #[arg(short, long, default_value = "models/")]
-        model_path: PathBuf,
+        #[command(flatten)]
+        project: ProjectArgs,
     },
     /// Launch the terminal UI
     Tui,
     /// Run models with dependency resolution
     Run {
-        /// Directory containing SQL model files
-        #[arg(short, long, default_value = "models/")]
-        model_path: PathBuf,
-        /// Specific model to run (if not specified, runs all models)
-        #[arg(short = 'n', long)]
-        model_name: Option<String>,
-        /// Include upstream dependencies
-        #[arg(short = 'u', long)]
-        upstream: bool,
-        /// Include downstream dependents
-        #[arg(short = 'd', long)]
-        downstream: bool,
+        #[command(flatten)]
+        project: ProjectArgs,
+        #[command(flatten)]
+        selection: SelectionArgs,
         /// Dry run (show execution plan without running)
         #[arg(long)]
         dry_run: bool,
         /// Fail fast on first error
         #[arg(long)]
         fail_fast: bool,
-        /// Database connection string
-        #[arg(short = 'c', long)]
-        connection: String,
+        /// Rebuild incremental models from scratch
+        #[arg(long)]
+        full_refresh: bool,
+        /// Rerun only the models that didn't succeed in the previous run, and their dependents
+        #[arg(long, conflicts_with_all = ["select", "exclude", "model_name"])]
+        retry_failed: bool,
+        /// Number of models to run concurrently (defaults to the target's threads, then 1)
+        #[arg(long, value_name = "N", value_parser = parse_threads)]
+        threads: Option<usize>,
+        /// Retries for models failing with a transient error such as a lost connection (defaults to the target's retries, then 0)
+        #[arg(long, value_name = "N")]
+        retries: Option<u32>,
+        /// Cancel models running longer than this, e.g. 90s or 10m (a model's timeout directive wins)
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+        timeout: Option<Duration>,
+        #[command(flatten)]
+        connection: ConnectionArgs,
+    },
+    /// Compile models into the SQL that would run, written to target/compiled/
+    Compile {
+        #[command(flatten)]
+        project: ProjectArgs,
+        #[command(flatten)]
+        selection: SelectionArgs,
+        /// Target from profiles.toml to compile for (defaults to its default_target).
+        /// Only its dialect and schema prefix are used, nothing is connected to.
+        #[arg(short = 't', long)]
+        target: Option<String>,
+    },
+    /// List the selected models in execution order
+    List {
+        #[command(flatten)]
+        project: ProjectArgs,
+        #[command(flatten)]
+        selection: SelectionArgs,
+    },
+    /// Export the dependency graph of the selected models and their sources
+    Graph {
+        #[command(flatten)]
+        project: ProjectArgs,
+        #[command(flatten)]
+        selection: SelectionArgs,
+        /// Format to export the graph in
+        #[arg(long, value_enum, default_value_t = GraphFormat::Dot)]
+        format: GraphFormat,
+        /// File to write the graph to (defaults to stdout)
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Run snapshot models, maintaining their history tables
+    Snapshot {
+        #[command(flatten)]
+        project: ProjectArgs,
+        #[command(flatten)]
+        selection: SelectionArgs,
+        /// Dry run (show execution plan without running)
+        #[arg(long)]
+        dry_run: bool,
+        /// Fail fast on first error
+        #[arg(long)]
+        fail_fast: bool,
+        /// Number of snapshots to run concurrently (defaults to the target's threads, then 1)
+        #[arg(long, value_name = "N", value_parser = parse_threads)]
+        threads: Option<usize>,
+        /// Retries for snapshots failing with a transient error such as a lost connection (defaults to the target's retries, then 0)
+        #[arg(long, value_name = "N")]
+        retries: Option<u32>,
+        /// Cancel snapshots running longer than this, e.g. 90s or 10m (a model's timeout directive wins)
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+        timeout: Option<Duration>,
+        #[command(flatten)]
+        connection: ConnectionArgs,
     },
 }
 
-#[derive(Parser, Debug)]
-pub struct RunCmdArgs {
-    #[arg(short, long, default_value = "models/")]
-    model_path: String,
+/// Flags shared by commands that work on the project's models, overriding cadac.toml
+#[derive(Args, Debug)]
+pub struct ProjectArgs {
+    /// Directory containing SQL model files (defaults to project.model_path in cadac.toml, then models/)
+    #[arg(short, long)]
+    pub model_path: Option<PathBuf>,
+    /// Set a project variable, overriding its value in cadac.toml
+    #[arg(long = "var", value_name = "KEY=VALUE")]
+    pub vars: Vec<String>,
+}
+
+/// Flags selecting the models a command works on
+#[derive(Args, Debug)]
+pub struct SelectionArgs {
+    /// Models to select (defaults to all), e.g. gold.orders, +gold.orders, bronze.users+2,
+    /// schema:bronze, tag:finance or path:models/gold. Selectors separated by spaces are
+    /// combined; selectors joined by commas must all match
+    #[arg(short = 's', long, value_name = "SELECTOR", num_args = 1..)]
+    pub select: Vec<String>,
+    /// Models to leave out of the selection, using the same selectors as --select
+    #[arg(long, value_name = "SELECTOR", num_args = 1..)]
+    pub exclude: Vec<String>,
+    /// Single model to select, kept from before --select existed
+    #[arg(short = 'n', long, hide = true, conflicts_with = "select")]
+    pub model_name: Option<String>,
+    /// With --model-name, also select the model's direct dependencies
+    #[arg(short = 'u', long, hide = true, requires = "model_name")]
+    pub upstream: bool,
+    /// With --model-name, also select the model's direct dependents
+    #[arg(short = 'd', long, hide = true, requires = "model_name")]
+    pub downstream: bool,
+}
+
+impl SelectionArgs {
+    /// Selectors choosing the models, with --model-name and its --upstream and
+    /// --downstream flags turned into the selector they stand for
+    pub fn selectors(&self) -> Vec<String> {
+        match &self.model_name {
+            Some(model) => {
+                let upstream = if self.upstream { "1+" } else { "" };
+                let downstream = if self.downstream { "+1" } else { "" };
+                vec![format!("{}{}{}", upstream, model, downstream)]
+            }
+            None => self.select.clone(),
+        }
+    }
+}
+
+/// Formats the dependency graph can be exported in
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GraphFormat {
+    /// Graphviz DOT
+    Dot,
+    /// Mermaid flowchart
+    Mermaid,
+    /// JSON list of nodes and edges
+    Json,
+}
+
+/// Flags selecting the database that models run against
+#[derive(Args, Debug)]
+pub struct ConnectionArgs {
+    /// Target from profiles.toml to run against (defaults to its default_target)
+    #[arg(short = 't', long)]
+    pub target: Option<String>,
+    /// Database connection string, used instead of a profiles.toml target
+    #[arg(short = 'c', long, conflicts_with = "target")]
+    pub connection: Option<String>,
+}
+
+/// Parse a thread count, which must be at least 1
+fn parse_threads(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(0) => Err("must be at least 1".to_string()),
+        Ok(threads) => Ok(threads),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_threads_must_be_positive() {
+        let args = BaseCliArgs::try_parse_from(["cadac", "run", "--threads", "4"]).unwrap();
+        assert!(matches!(args.command, Commands::Run { threads: Some(4), .. }));
+
+        assert!(BaseCliArgs::try_parse_from(["cadac", "run", "--threads", "0"]).is_err());
+        assert!(BaseCliArgs::try_parse_from(["cadac", "snapshot", "--threads", "0"]).is_err());
+    }
+
+    #[test]
+    fn test_model_name_flags_become_selectors() {
+        let selectors = |args: &[&str]| match BaseCliArgs::try_parse_from(args).unwrap().command {
+            Commands::Run { selection, .. } => selection.selectors(),
+            command => panic!("unexpected command {:?}", command),
+        };
+
+        assert_eq!(selectors(&["cadac", "run", "-n", "gold.orders"]), vec!["gold.orders"]);
+        assert_eq!(
+            selectors(&["cadac", "run", "-n", "gold.orders", "--upstream"]),
+            vec!["1+gold.orders"]
+        );
+        assert_eq!(
+            selectors(&["cadac", "run", "--model-name", "gold.orders", "-u", "-d"]),
+            vec!["1+gold.orders+1"]
+        );
+        assert_eq!(
+            selectors(&["cadac", "run", "--select", "tag:finance"]),
+            vec!["tag:finance"]
+        );
+
+        assert!(BaseCliArgs::try_parse_from(["cadac", "run", "--upstream"]).is_err());
+        assert!(BaseCliArgs::try_parse_from(["cadac", "run", "-n", "a", "-s", "b"]).is_err());
+    }
 }
@@ -0,0 +1,280 @@
+use color_eyre::Result;
+use color_eyre::eyre::{Context, eyre};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::discovery::ModelCatalog;
+use crate::execution::SqlDialect;
+use crate::execution::materialization::{self, ModelBuild, normalize_select};
+use crate::parser::{Materialization, SourceReference};
+
+/// Prefix of the CTE names that ephemeral models are inlined as
+const EPHEMERAL_CTE_PREFIX: &str = "__cadac_ephemeral__";
+
+/// Directory compiled models are written to, relative to the project root
+pub const COMPILED_DIR: &str = "target/compiled";
+
+/// Describe how to build a model, with its SQL compiled
+pub fn model_build(
+    catalog: &ModelCatalog,
+    model_name: &str,
+    full_refresh: bool,
+) -> Result<ModelBuild> {
+    let relation = catalog
+        .relation(model_name)
+        .ok_or_else(|| eyre!("Unknown model: {}", model_name))?;
+    let config = catalog
+        .models
+        .get(model_name)
+        .map(|model| model.config.clone())
+        .unwrap_or_default();
+
+    Ok(ModelBuild {
+        relation,
+        select_sql: compile_model(catalog, model_name)?,
+        materialization: catalog.get_materialization(model_name),
+        config,
+        full_refresh,
+        dependent_views: catalog
+            .get_dependent_views(model_name)
+            .iter()
+            .filter_map(|view| catalog.relation(view))
+            .collect(),
+    })
+}
+
+/// Write the executable SQL of the selected models to `<output_dir>/<schema>/<table>.sql`,
+/// replacing their previous output and leaving that of other models alone.
+/// Models are compiled for the dialect as on their first build; ephemeral
+/// models are written as the SELECT their dependents inline.
+pub fn write_compiled_models(
+    catalog: &ModelCatalog,
+    selected: &BTreeSet<String>,
+    dialect: &SqlDialect,
+    output_dir: &Path,
+) -> Result<Vec<PathBuf>> {
+    let mut written = Vec::new();
+    for model_name in selected {
+        let model_identity = &catalog.model_identities[model_name];
+        let compiled_sql = if catalog.get_materialization(model_name) == Materialization::Ephemeral
+        {
+            compile_model(catalog, model_name)?
+        } else {
+            let build = model_build(catalog, model_name, false)?;
+            let statements = materialization::build_statements(dialect, &build, None, &[])?;
+            statements
+                .statements
+                .iter()
+                .map(|statement| format!("{};\n", statement.trim_end()))
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+
+        let schema_dir = output_dir.join(&model_identity.schema_name);
+        fs::create_dir_all(&schema_dir)
+            .wrap_err_with(|| format!("Failed to create directory: {:?}", schema_dir))?;
+        let compiled_path = schema_dir.join(format!("{}.sql", model_identity.table_name));
+        fs::write(&compiled_path, compiled_sql)
+            .wrap_err_with(|| format!("Failed to write compiled model: {:?}", compiled_path))?;
+        written.push(compiled_path);
+    }
+
+    Ok(written)
+}
+
+/// Compile a model into the SQL that is executed against the database,
+/// inlining its ephemeral dependencies as CTEs and pointing references to
+/// other models at the relations they are built as
+pub fn compile_model(catalog: &ModelCatalog, model_name: &str) -> Result<String> {
+    let sql = rewrite_model_references(catalog, model_name)?;
+
+    let ephemeral_models = catalog
+        .dependency_graph
+        .get_ephemeral_dependencies(model_name);
+    if ephemeral_models.is_empty() {
+        return Ok(sql);
+    }
+
+    let mut ctes = Vec::new();
+    for ephemeral_model in &ephemeral_models {
+        let select_sql = rewrite_model_references(catalog, ephemeral_model)?;
+        ctes.push((
+            ephemeral_cte_name(ephemeral_model),
+            normalize_select(&select_sql).to_string(),
+        ));
+    }
+
+    Ok(inject_ctes(&sql, &ctes))
+}
+
+/// Read a model's SQL and point its references to other models at their
+/// CTEs (ephemeral models) or relations
+fn rewrite_model_references(catalog: &ModelCatalog, model_name: &str) -> Result<String> {
+    let model_identity = catalog
+        .model_identities
+        .get(model_name)
+        .ok_or_else(|| eyre!("Unknown model: {}", model_name))?;
+    let sql = catalog.read_model_sql(model_identity)?;
+
+    let references = catalog
+        .models
+        .get(model_name)
+        .map(|model| model.references.as_slice())
+        .unwrap_or_default();
+
+    // Unqualified references resolve relative to the model, so resolve them up front
+    let mut resolved = HashMap::new();
+    for reference in references {
+        if let Some(model) = catalog.resolve_reference(model_name, &reference.source_id)? {
+            resolved.insert(reference.source_id.as_str(), model);
+        }
+    }
+
+    Ok(rewrite_references(&sql, references, |source_id| {
+        let model = resolved.get(source_id)?;
+        if catalog.dependency_graph.is_ephemeral(model) {
+            return Some(ephemeral_cte_name(model));
+        }
+
+        // Only references whose relation differs from the reference need rewriting
+        catalog
+            .relation(model)
+            .map(|relation| relation.to_string())
+            .filter(|relation| relation != source_id)
+    }))
+}
+
+/// Replace table references in the SQL with the text returned by `rewrite`,
+/// leaving references it returns None for untouched
+pub fn rewrite_references(
+    sql: &str,
+    references: &[SourceReference],
+    rewrite: impl Fn(&str) -> Option<String>,
+) -> String {
+    let mut references: Vec<&SourceReference> = references.iter().collect();
+    references.sort_by_key(|reference| reference.byte_range.start);
+
+    let mut compiled = String::with_capacity(sql.len());
+    let mut position = 0;
+    for reference in references {
+        if let Some(replacement) = rewrite(&reference.source_id) {
+            compiled.push_str(&sql[position..reference.byte_range.start]);
+            compiled.push_str(&replacement);
+            position = reference.byte_range.end;
+        }
+    }
+    compiled.push_str(&sql[position..]);
+
+    compiled
+}
+
+/// Name of the CTE an ephemeral model is inlined as
+fn ephemeral_cte_name(model_name: &str) -> String {
+    format!("{}{}", EPHEMERAL_CTE_PREFIX, model_name.replace('.', "__"))
+}
+
+/// Prepend CTEs to a statement, merging them into its WITH clause if it has one
+fn inject_ctes(sql: &str, ctes: &[(String, String)]) -> String {
+    let (leading, statement) = sql.split_at(statement_start(sql));
+    let definitions = ctes
+        .iter()
+        .map(|(name, select_sql)| format!("{} AS (\n{}\n)", name, select_sql))
+        .collect::<Vec<_>>()
+        .join(",\n");
+
+    match strip_keyword(statement, "WITH") {
+        Some(rest) => match strip_keyword(rest, "RECURSIVE") {
+            Some(rest) => format!("{}WITH RECURSIVE {},\n{}", leading, definitions, rest),
+            None => format!("{}WITH {},\n{}", leading, definitions, rest),
+        },
+        None => format!("{}WITH {}\n{}", leading, definitions, statement),
+    }
+}
+
+/// Strip a leading keyword (case-insensitive) and the whitespace after it
+fn strip_keyword<'a>(sql: &'a str, keyword: &str) -> Option<&'a str> {
+    let head = sql.get(..keyword.len())?;
+    let rest = &sql[keyword.len()..];
+    (head.eq_ignore_ascii_case(keyword) && rest.starts_with(char::is_whitespace))
+        .then(|| rest.trim_start())
+}
+
+/// Byte offset of the first token after any leading whitespace and comments
+fn statement_start(sql: &str) -> usize {
+    let mut rest = sql;
+    loop {
+        let trimmed = rest.trim_start();
+        if let Some(comment) = trimmed.strip_prefix("--") {
+            rest = comment.split_once('\n').map_or("", |(_, after)| after);
+        } else if let Some(comment) = trimmed.strip_prefix("/*") {
+            rest = comment.split_once("*/").map_or("", |(_, after)| after);
+        } else {
+            return sql.len() - trimmed.len();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reference(source_id: &str, sql: &str) -> SourceReference {
+        let start = sql.find(source_id).unwrap();
+        SourceReference {
+            source_id: source_id.to_string(),
+            byte_range: start..start + source_id.len(),
+            line: 1,
+        }
+    }
+
+    #[test]
+    fn test_rewrite_references() {
+        let sql = "SELECT * FROM staging.users u JOIN raw_orders o ON u.id = o.user_id";
+        let references = vec![
+            reference("raw_orders", sql),
+            reference("staging.users", sql),
+        ];
+
+        let compiled = rewrite_references(sql, &references, |source_id| {
+            (source_id == "staging.users").then(|| "cte_users".to_string())
+        });
+
+        assert_eq!(
+            compiled,
+            "SELECT * FROM cte_users u JOIN raw_orders o ON u.id = o.user_id"
+        );
+    }
+
+    #[test]
+    fn test_inject_ctes() {
+        let ctes = vec![("cte_users".to_string(), "SELECT 1 AS id".to_string())];
+
+        // Header comments stay at the top so they still describe the model
+        let sql = "-- Orders\n-- cadac: materialized=view\nSELECT * FROM cte_users";
+        assert_eq!(
+            inject_ctes(sql, &ctes),
+            "-- Orders\n-- cadac: materialized=view\nWITH cte_users AS (\nSELECT 1 AS id\n)\nSELECT * FROM cte_users"
+        );
+
+        let sql = "with recursive numbers AS (SELECT 1) SELECT * FROM numbers, cte_users";
+        assert_eq!(
+            inject_ctes(sql, &ctes),
+            "WITH RECURSIVE cte_users AS (\nSELECT 1 AS id\n),\nnumbers AS (SELECT 1) SELECT * FROM numbers, cte_users"
+        );
+
+        let sql = "/* note */ SELECT * FROM cte_users";
+        assert!(inject_ctes(sql, &ctes).starts_with("/* note */ WITH cte_users AS ("));
+
+        // Only a whole WITH keyword starts a WITH clause
+        assert_eq!(strip_keyword("without_users", "WITH"), None);
+    }
+
+    #[test]
+    fn test_ephemeral_cte_name() {
+        assert_eq!(
+            ephemeral_cte_name("staging.users"),
+            "__cadac_ephemeral__staging__users"
+        );
+    }
+}
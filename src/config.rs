@@ -0,0 +1,261 @@
+use color_eyre::Result;
+use color_eyre::eyre::{Context, eyre};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::parser::Materialization;
+
+/// Name of the project configuration file at the project root
+pub const PROJECT_CONFIG_FILE: &str = "cadac.toml";
+
+/// Model directory used when neither the CLI nor the project config sets one
+const DEFAULT_MODEL_PATH: &str = "models/";
+
+/// Project configuration loaded from `cadac.toml`
+///
+/// ```toml
+/// [project]
+/// model_path = "models/"
+/// connection = "postgresql://localhost/warehouse"
+/// search_path = ["staging", "bronze"]
+///
+/// [models]
+/// materialized = "view"
+///
+/// [schemas.gold]
+/// materialized = "table"
+/// target_schema = "analytics"
+/// tags = ["reporting"]
+///
+/// [vars]
+/// start_date = "2024-01-01"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectConfig {
+    pub project: ProjectSettings,
+    pub models: ModelDefaults,
+    /// Settings for the models in a schema folder, keyed by folder name
+    pub schemas: HashMap<String, SchemaConfig>,
+    /// Variables substituted into model SQL as `{{ name }}`
+    pub vars: HashMap<String, toml::Value>,
+}
+
+/// Project-wide settings, each of which can be overridden by a CLI flag
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectSettings {
+    /// Directory containing SQL model files
+    pub model_path: Option<PathBuf>,
+    /// Database connection string, used when no profile target is selected
+    pub connection: Option<String>,
+    /// Schema folders searched for unqualified table references that don't
+    /// name a model in the referencing model's own schema
+    pub search_path: Vec<String>,
+}
+
+/// Defaults for every model in the project
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ModelDefaults {
+    /// Materialization for models that don't configure one
+    pub materialized: Option<Materialization>,
+}
+
+/// Defaults for the models in one schema folder
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SchemaConfig {
+    /// Materialization for models in the folder that don't configure one
+    pub materialized: Option<Materialization>,
+    /// Database schema the folder's models are built in, defaults to the folder name
+    pub target_schema: Option<String>,
+    /// Database the folder's models are built in, on platforms that reference across databases
+    pub database: Option<String>,
+    /// Tags added to every model in the folder
+    pub tags: Vec<String>,
+}
+
+impl ProjectConfig {
+    /// Load `cadac.toml` from the project directory, using the defaults when it doesn't exist
+    pub fn load(project_dir: &Path) -> Result<Self> {
+        let config_path = project_dir.join(PROJECT_CONFIG_FILE);
+        if !config_path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = fs::read_to_string(&config_path)
+            .wrap_err_with(|| format!("Failed to read project config: {:?}", config_path))?;
+        toml::from_str(&content)
+            .wrap_err_with(|| format!("Failed to parse project config: {:?}", config_path))
+    }
+
+    /// Resolve the model directory, preferring the CLI flag over the project config
+    pub fn model_path(&self, cli_model_path: Option<PathBuf>) -> PathBuf {
+        cli_model_path
+            .or_else(|| self.project.model_path.clone())
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_MODEL_PATH))
+    }
+
+    /// Override variables with `key=value` pairs given on the command line
+    pub fn apply_cli_vars(&mut self, cli_vars: &[String]) -> Result<()> {
+        for var in cli_vars {
+            let (name, value) = var
+                .split_once('=')
+                .ok_or_else(|| eyre!("Invalid variable '{}', expected key=value", var))?;
+            self.vars
+                .insert(name.trim().to_string(), toml::Value::String(value.to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// Materialization for a model in the given schema folder that doesn't configure one
+    pub fn default_materialization(&self, schema: &str) -> Materialization {
+        self.schemas
+            .get(schema)
+            .and_then(|schema_config| schema_config.materialized)
+            .or(self.models.materialized)
+            .unwrap_or(Materialization::Table)
+    }
+
+    /// Database schema that models in the given schema folder are built in
+    pub fn target_schema<'a>(&'a self, schema: &'a str) -> &'a str {
+        self.schemas
+            .get(schema)
+            .and_then(|schema_config| schema_config.target_schema.as_deref())
+            .unwrap_or(schema)
+    }
+
+    /// Database that models in the given schema folder are built in, if configured
+    pub fn target_database(&self, schema: &str) -> Option<&str> {
+        self.schemas
+            .get(schema)
+            .and_then(|schema_config| schema_config.database.as_deref())
+    }
+
+    /// Tags shared by every model in the given schema folder
+    pub fn schema_tags(&self, schema: &str) -> &[String] {
+        self.schemas
+            .get(schema)
+            .map(|schema_config| schema_config.tags.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Replace `{{ name }}` placeholders in model SQL with project variables.
+    /// Only braces around a plain variable name are placeholders; any other
+    /// braces, e.g. in JSON literals, are left as they are.
+    pub fn substitute_vars(&self, sql: &str) -> Result<String> {
+        let mut substituted = String::with_capacity(sql.len());
+        let mut rest = sql;
+
+        while let Some(start) = rest.find("{{") {
+            let Some((name, len)) = placeholder(&rest[start..]) else {
+                substituted.push_str(&rest[..start + 2]);
+                rest = &rest[start + 2..];
+                continue;
+            };
+            let value = self
+                .vars
+                .get(name)
+                .ok_or_else(|| eyre!("Undefined variable '{}'", name))?;
+
+            substituted.push_str(&rest[..start]);
+            match value {
+                toml::Value::String(value) => substituted.push_str(value),
+                value => substituted.push_str(&value.to_string()),
+            }
+            rest = &rest[start + len..];
+        }
+        substituted.push_str(rest);
+
+        Ok(substituted)
+    }
+}
+
+/// Variable name and length of the `{{ name }}` placeholder at the start of
+/// `text`, if it is one
+fn placeholder(text: &str) -> Option<(&str, usize)> {
+    let end = text.find("}}")?;
+    let name = text[2..end].trim();
+    let is_identifier = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+    is_identifier.then_some((name, end + 2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+[project]
+model_path = "warehouse/models"
+search_path = ["staging"]
+
+[models]
+materialized = "view"
+
+[schemas.gold]
+materialized = "table"
+target_schema = "analytics"
+tags = ["reporting"]
+
+[vars]
+start_date = "2024-01-01"
+min_amount = 10
+"#;
+
+    #[test]
+    fn test_parse_project_config() -> Result<()> {
+        let config: ProjectConfig = toml::from_str(CONFIG)?;
+
+        assert_eq!(config.default_materialization("gold"), Materialization::Table);
+        assert_eq!(config.default_materialization("bronze"), Materialization::View);
+        assert_eq!(config.target_schema("gold"), "analytics");
+        assert_eq!(config.target_schema("bronze"), "bronze");
+        assert_eq!(config.schema_tags("gold"), ["reporting"]);
+        assert_eq!(config.project.search_path, ["staging"]);
+
+        // Unknown keys are rejected rather than silently ignored
+        assert!(toml::from_str::<ProjectConfig>("[schemas.gold]\nmaterialised = \"view\"").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cli_overrides_project_config() -> Result<()> {
+        let mut config: ProjectConfig = toml::from_str(CONFIG)?;
+
+        assert_eq!(config.model_path(None), PathBuf::from("warehouse/models"));
+        assert_eq!(
+            config.model_path(Some(PathBuf::from("other"))),
+            PathBuf::from("other")
+        );
+        assert_eq!(ProjectConfig::default().model_path(None), PathBuf::from("models/"));
+
+        config.apply_cli_vars(&["start_date=2025-01-01".to_string()])?;
+        assert_eq!(
+            config.substitute_vars("WHERE day >= '{{ start_date }}' AND amount > {{min_amount}}")?,
+            "WHERE day >= '2025-01-01' AND amount > 10"
+        );
+        assert!(config.substitute_vars("SELECT {{ missing }}").is_err());
+
+        // Braces that don't wrap a variable name are not placeholders
+        for sql in [
+            "SELECT '{{\"a\": 1}}'::jsonb",
+            "SELECT '{{' AS open",
+            "SELECT '{{}}', '{{ min amount }}'",
+        ] {
+            assert_eq!(config.substitute_vars(sql)?, sql);
+        }
+        assert_eq!(
+            config.substitute_vars("SELECT '{{' || {{ min_amount }}")?,
+            "SELECT '{{' || 10"
+        );
+
+        Ok(())
+    }
+}
@@ -3,9 +3,12 @@ use color_eyre::eyre::{Context, eyre};
 use petgraph::algo::{is_cyclic_directed, toposort};
 use petgraph::graph::NodeIndex;
 use petgraph::{Direction, Graph};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::path::{Path, PathBuf};
 
+/// Most circular dependencies reported at once
+pub const MAX_REPORTED_CYCLES: usize = 20;
+
 /// Represents the identity of a model based on its file path and schema organization
 #[derive(Debug, Clone, PartialEq)]
 pub struct ModelIdentity {
@@ -57,6 +60,27 @@ impl ModelIdentity {
     }
 }
 
+/// A model reached by walking the dependency graph from another model
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelPath {
+    /// The model that was reached
+    pub model: String,
+    /// The models along the way, from the starting model to the reached one
+    pub path: Vec<String>,
+}
+
+impl ModelPath {
+    /// Number of edges between the starting model and the reached one
+    pub fn depth(&self) -> usize {
+        self.path.len() - 1
+    }
+
+    /// The models between the starting model and the reached one
+    pub fn via(&self) -> &[String] {
+        &self.path[1..self.path.len() - 1]
+    }
+}
+
 /// Represents a dependency graph of models using petgraph
 #[derive(Debug)]
 pub struct DependencyGraph {
@@ -65,6 +89,8 @@ pub struct DependencyGraph {
     graph: Graph<String, ()>,
     /// Map from qualified model name to node index for efficient lookups
     node_indices: HashMap<String, NodeIndex>,
+    /// Models that are inlined into their dependents instead of being executed
+    ephemeral: HashSet<String>,
 }
 
 impl DependencyGraph {
@@ -73,6 +99,7 @@ impl DependencyGraph {
         Self {
             graph: Graph::new(),
             node_indices: HashMap::new(),
+            ephemeral: HashSet::new(),
         }
     }
 
@@ -101,6 +128,17 @@ impl DependencyGraph {
         Ok(())
     }
 
+    /// Mark a model as ephemeral so it is left out of the execution order
+    pub fn mark_ephemeral(&mut self, qualified_name: &str) {
+        self.add_model(qualified_name);
+        self.ephemeral.insert(qualified_name.to_string());
+    }
+
+    /// Check if a model is ephemeral
+    pub fn is_ephemeral(&self, model: &str) -> bool {
+        self.ephemeral.contains(model)
+    }
+
     /// Check if the dependency graph has any cycles
     pub fn has_cycles(&self) -> bool {
         is_cyclic_directed(&self.graph)
@@ -108,21 +146,135 @@ impl DependencyGraph {
 
     /// Get the execution order of models using topological sorting
     /// Returns models in the order they should be executed (dependencies first)
+    /// Ephemeral models take part in the ordering but are never executed
     pub fn execution_order(&self) -> Result<Vec<String>> {
+        Ok(self
+            .topological_order()?
+            .into_iter()
+            .filter(|model| !self.is_ephemeral(model))
+            .collect())
+    }
+
+    /// Get every model, ephemeral ones included, with dependencies first
+    pub fn topological_order(&self) -> Result<Vec<String>> {
         match toposort(&self.graph, None) {
             Ok(node_indices) => {
-                let mut execution_order: Vec<String> = node_indices
+                let mut order: Vec<String> = node_indices
                     .iter()
                     .map(|&idx| self.graph[idx].clone())
                     .collect();
                 // Reverse to get proper execution order (dependencies first)
-                execution_order.reverse();
-                Ok(execution_order)
+                order.reverse();
+                Ok(order)
+            }
+            Err(_) => {
+                let (cycles, truncated) = self.reported_cycles();
+                let mut cycles: Vec<String> =
+                    cycles.iter().map(|cycle| cycle.join(" -> ")).collect();
+                if truncated {
+                    cycles.push(format!(
+                        "and more, only the first {} are listed",
+                        MAX_REPORTED_CYCLES
+                    ));
+                }
+                Err(eyre!(
+                    "Cannot determine execution order: circular dependency detected: {}",
+                    cycles.join("; ")
+                ))
+            }
+        }
+    }
+
+    /// Find up to `limit` circular dependencies in the graph. Every elementary
+    /// cycle is reported once, starting and ending at its first model by name,
+    /// e.g. `["a", "b", "a"]` when a and b select from each other.
+    ///
+    /// Cycles are enumerated with Johnson's algorithm, by their first model
+    /// and then by name, so the same ones are reported on every run.
+    pub fn find_cycles(&self, limit: usize) -> Vec<Vec<String>> {
+        let mut nodes: Vec<NodeIndex> = self.graph.node_indices().collect();
+        nodes.sort_by(|&a, &b| self.graph[a].cmp(&self.graph[b]));
+        let rank: HashMap<NodeIndex, usize> = nodes
+            .iter()
+            .enumerate()
+            .map(|(rank, &node_idx)| (node_idx, rank))
+            .collect();
+
+        let mut cycles = Vec::new();
+        for (start_rank, &start) in nodes.iter().enumerate() {
+            if cycles.len() >= limit {
+                break;
+            }
+            // Cycles through earlier models have been found already, so only
+            // the later models strongly connected to the start are searched
+            let later = |node_idx: &NodeIndex| rank[node_idx] >= start_rank;
+            let downstream = self.reachable(start, Direction::Outgoing, later);
+            let component: HashSet<NodeIndex> = self
+                .reachable(start, Direction::Incoming, later)
+                .intersection(&downstream)
+                .copied()
+                .collect();
+
+            let mut search = CycleSearch {
+                graph: self,
+                start,
+                component,
+                path: vec![start],
+                blocked: HashSet::new(),
+                blocked_by: HashMap::new(),
+                cycles: &mut cycles,
+                limit,
+            };
+            search.circuit(start);
+        }
+
+        let mut cycles: Vec<Vec<String>> = cycles
+            .iter()
+            .map(|cycle| cycle.iter().map(|&idx| self.graph[idx].clone()).collect())
+            .collect();
+        cycles.sort();
+        cycles
+    }
+
+    /// The cycles to report to the user, at most MAX_REPORTED_CYCLES, and
+    /// whether there are more, as densely connected models can form a huge
+    /// number of them
+    pub fn reported_cycles(&self) -> (Vec<Vec<String>>, bool) {
+        let mut cycles = self.find_cycles(MAX_REPORTED_CYCLES + 1);
+        let truncated = cycles.len() > MAX_REPORTED_CYCLES;
+        cycles.truncate(MAX_REPORTED_CYCLES);
+        (cycles, truncated)
+    }
+
+    // Models reachable from the given one in the direction, only going through
+    // models accepted by the filter. The model itself is included.
+    fn reachable(
+        &self,
+        from: NodeIndex,
+        direction: Direction,
+        filter: impl Fn(&NodeIndex) -> bool,
+    ) -> HashSet<NodeIndex> {
+        let mut visited = HashSet::from([from]);
+        let mut queue = VecDeque::from([from]);
+        while let Some(node_idx) = queue.pop_front() {
+            for neighbor in self.graph.neighbors_directed(node_idx, direction) {
+                if filter(&neighbor) && visited.insert(neighbor) {
+                    queue.push_back(neighbor);
+                }
             }
-            Err(_) => Err(eyre!(
-                "Cannot determine execution order: circular dependency detected"
-            )),
         }
+        visited
+    }
+
+    // Dependencies of a model sorted by name, so cycles are found in a stable order
+    fn sorted_dependencies(&self, node_idx: NodeIndex) -> Vec<NodeIndex> {
+        let mut dependencies: Vec<NodeIndex> = self
+            .graph
+            .neighbors_directed(node_idx, Direction::Outgoing)
+            .collect();
+        dependencies.sort_by(|&a, &b| self.graph[a].cmp(&self.graph[b]));
+        dependencies.dedup();
+        dependencies
     }
 
     /// Get all models that depend on the given model (impact analysis)
@@ -137,6 +289,65 @@ impl DependencyGraph {
         }
     }
 
+    /// Get the models upstream of the given model, up to `depth` dependencies
+    /// away or all of its ancestors when no depth is given, sorted by name
+    pub fn get_upstream(&self, model: &str, depth: Option<usize>) -> Vec<String> {
+        Self::names(self.get_upstream_paths(model, depth))
+    }
+
+    /// Get the models downstream of the given model, up to `depth` dependents
+    /// away or all of its descendants when no depth is given, sorted by name
+    pub fn get_downstream(&self, model: &str, depth: Option<usize>) -> Vec<String> {
+        Self::names(self.get_downstream_paths(model, depth))
+    }
+
+    /// Like `get_upstream`, with the chain of dependencies leading to each ancestor
+    pub fn get_upstream_paths(&self, model: &str, depth: Option<usize>) -> Vec<ModelPath> {
+        self.walk(model, Direction::Outgoing, depth)
+    }
+
+    /// Like `get_downstream`, with the chain of dependents leading to each descendant
+    pub fn get_downstream_paths(&self, model: &str, depth: Option<usize>) -> Vec<ModelPath> {
+        self.walk(model, Direction::Incoming, depth)
+    }
+
+    fn names(paths: Vec<ModelPath>) -> Vec<String> {
+        paths.into_iter().map(|path| path.model).collect()
+    }
+
+    // Breadth-first walk so each model is reached along one of its shortest paths
+    fn walk(&self, model: &str, direction: Direction, depth: Option<usize>) -> Vec<ModelPath> {
+        let Some(&start) = self.node_indices.get(model) else {
+            return Vec::new();
+        };
+
+        let mut visited = HashSet::from([start]);
+        let mut frontier = vec![vec![start]];
+        let mut distance = 0;
+        let mut found = Vec::new();
+        while !frontier.is_empty() && depth.is_none_or(|depth| distance < depth) {
+            distance += 1;
+            let mut next = Vec::new();
+            for path in frontier {
+                let last = path[path.len() - 1];
+                for neighbor in self.graph.neighbors_directed(last, direction) {
+                    if visited.insert(neighbor) {
+                        let mut path = path.clone();
+                        path.push(neighbor);
+                        found.push(ModelPath {
+                            model: self.graph[neighbor].clone(),
+                            path: path.iter().map(|&idx| self.graph[idx].clone()).collect(),
+                        });
+                        next.push(path);
+                    }
+                }
+            }
+            frontier = next;
+        }
+        found.sort_by(|a, b| a.model.cmp(&b.model));
+        found
+    }
+
     /// Get all models that the given model depends on (lineage tracking)
     pub fn get_dependencies(&self, model: &str) -> Vec<String> {
         if let Some(&node_idx) = self.node_indices.get(model) {
@@ -149,6 +360,33 @@ impl DependencyGraph {
         }
     }
 
+    /// Get the ephemeral models that must be inlined into the given model,
+    /// following chains of ephemeral dependencies, ordered dependencies first
+    pub fn get_ephemeral_dependencies(&self, model: &str) -> Vec<String> {
+        let mut ordered = Vec::new();
+        if let Some(&node_idx) = self.node_indices.get(model) {
+            let mut visited = HashSet::new();
+            self.collect_ephemeral_dependencies(node_idx, &mut visited, &mut ordered);
+        }
+        ordered
+    }
+
+    // Depth-first post-order walk so every ephemeral model follows its own dependencies
+    fn collect_ephemeral_dependencies(
+        &self,
+        node_idx: NodeIndex,
+        visited: &mut HashSet<NodeIndex>,
+        ordered: &mut Vec<String>,
+    ) {
+        for dependency in self.graph.neighbors_directed(node_idx, Direction::Outgoing) {
+            let name = &self.graph[dependency];
+            if self.is_ephemeral(name) && visited.insert(dependency) {
+                self.collect_ephemeral_dependencies(dependency, visited, ordered);
+                ordered.push(name.clone());
+            }
+        }
+    }
+
     // /// Get all models in the graph
     // pub fn get_all_models(&self) -> Vec<String> {
     //     self.graph.node_weights().cloned().collect()
@@ -171,6 +409,70 @@ impl Default for DependencyGraph {
     }
 }
 
+/// State of Johnson's search for the elementary cycles through one model,
+/// within the models strongly connected to it
+struct CycleSearch<'a> {
+    graph: &'a DependencyGraph,
+    start: NodeIndex,
+    component: HashSet<NodeIndex>,
+    path: Vec<NodeIndex>,
+    /// Models that can't currently lead back to the start without revisiting the path
+    blocked: HashSet<NodeIndex>,
+    /// Models to unblock once the model they are keyed by is unblocked
+    blocked_by: HashMap<NodeIndex, HashSet<NodeIndex>>,
+    cycles: &'a mut Vec<Vec<NodeIndex>>,
+    limit: usize,
+}
+
+impl CycleSearch<'_> {
+    /// Extend the path from the given model, returning whether a cycle was found
+    fn circuit(&mut self, node_idx: NodeIndex) -> bool {
+        let mut found = false;
+        self.blocked.insert(node_idx);
+        for neighbor in self.graph.sorted_dependencies(node_idx) {
+            if self.cycles.len() >= self.limit {
+                return true;
+            }
+            if !self.component.contains(&neighbor) {
+                continue;
+            }
+            if neighbor == self.start {
+                let mut cycle = self.path.clone();
+                cycle.push(self.start);
+                self.cycles.push(cycle);
+                found = true;
+            } else if !self.blocked.contains(&neighbor) {
+                self.path.push(neighbor);
+                found |= self.circuit(neighbor);
+                self.path.pop();
+            }
+        }
+
+        if found {
+            self.unblock(node_idx);
+        } else {
+            for neighbor in self.graph.sorted_dependencies(node_idx) {
+                if self.component.contains(&neighbor) {
+                    self.blocked_by
+                        .entry(neighbor)
+                        .or_default()
+                        .insert(node_idx);
+                }
+            }
+        }
+        found
+    }
+
+    fn unblock(&mut self, node_idx: NodeIndex) {
+        self.blocked.remove(&node_idx);
+        for blocked in self.blocked_by.remove(&node_idx).unwrap_or_default() {
+            if self.blocked.contains(&blocked) {
+                self.unblock(blocked);
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -245,6 +547,30 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_ephemeral_models() -> Result<()> {
+        let mut graph = DependencyGraph::new();
+
+        // C -> B -> A, with B ephemeral and built on another ephemeral E
+        graph.add_dependency("B", "A")?;
+        graph.add_dependency("B", "E")?;
+        graph.add_dependency("C", "B")?;
+        graph.mark_ephemeral("B");
+        graph.mark_ephemeral("E");
+
+        // Ephemeral models are never executed but still order their dependents
+        assert_eq!(graph.execution_order()?, vec!["A", "C"]);
+        let order = graph.topological_order()?;
+        assert_eq!(order.len(), 4);
+        assert_eq!(order.last().map(String::as_str), Some("C"));
+        assert_eq!(graph.get_dependencies("C"), vec!["B"]);
+
+        assert_eq!(graph.get_ephemeral_dependencies("C"), vec!["E", "B"]);
+        assert!(graph.get_ephemeral_dependencies("A").is_empty());
+
+        Ok(())
+    }
+
     #[test]
     fn test_cycle_detection() -> Result<()> {
         let mut graph = DependencyGraph::new();
@@ -255,6 +581,69 @@ mod tests {
 
         assert!(graph.has_cycles());
         assert!(graph.execution_order().is_err());
+        assert_eq!(graph.find_cycles(10), vec![vec!["A", "B", "A"]]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cycle_paths() -> Result<()> {
+        let mut graph = DependencyGraph::new();
+
+        // A -> B -> C -> A, with a shortcut C -> B and an acyclic D -> A
+        graph.add_dependency("A", "B")?;
+        graph.add_dependency("B", "C")?;
+        graph.add_dependency("C", "A")?;
+        graph.add_dependency("C", "B")?;
+        graph.add_dependency("D", "A")?;
+        // A model selecting from itself is a cycle too
+        graph.add_dependency("E", "E")?;
+
+        // Every cycle is reported, including the one through the shortcut
+        assert_eq!(
+            graph.find_cycles(10),
+            vec![
+                vec!["A", "B", "C", "A"],
+                vec!["B", "C", "B"],
+                vec!["E", "E"]
+            ]
+        );
+        assert_eq!(graph.find_cycles(1), vec![vec!["A", "B", "C", "A"]]);
+
+        let error = graph.execution_order().unwrap_err().to_string();
+        assert!(error.contains("A -> B -> C -> A; B -> C -> B; E -> E"));
+
+        let mut graph = DependencyGraph::new();
+        graph.add_dependency("B", "A")?;
+        assert!(graph.find_cycles(10).is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reported_cycles_are_capped() -> Result<()> {
+        let mut graph = DependencyGraph::new();
+
+        // Five models all selecting from each other form 84 elementary cycles
+        let models = ["A", "B", "C", "D", "E"];
+        for from in models {
+            for to in models {
+                if from != to {
+                    graph.add_dependency(from, to)?;
+                }
+            }
+        }
+
+        let (cycles, truncated) = graph.reported_cycles();
+        assert!(truncated);
+        assert_eq!(cycles.len(), MAX_REPORTED_CYCLES);
+        assert_eq!(graph.find_cycles(100).len(), 84);
+
+        let error = graph.execution_order().unwrap_err().to_string();
+        assert!(error.ends_with(&format!(
+            "and more, only the first {} are listed",
+            MAX_REPORTED_CYCLES
+        )));
 
         Ok(())
     }
@@ -273,6 +662,17 @@ mod tests {
         assert!(dependents.contains(&"gold.orders".to_string()));
         assert!(dependents.contains(&"silver.customers".to_string()));
 
+        // Impact reaches models built on the direct dependents too
+        graph.add_dependency("gold.revenue", "gold.orders")?;
+        assert_eq!(
+            graph.get_downstream("bronze.users", None),
+            vec!["gold.orders", "gold.revenue", "silver.customers"]
+        );
+        assert_eq!(
+            graph.get_downstream("bronze.users", Some(1)),
+            vec!["gold.orders", "silver.customers"]
+        );
+
         Ok(())
     }
 
@@ -290,6 +690,54 @@ mod tests {
         let dependencies = graph.get_dependencies("bronze.users");
         assert_eq!(dependencies, vec!["sources.users"]);
 
+        assert_eq!(
+            graph.get_upstream("gold.orders", None),
+            vec!["bronze.users", "sources.users"]
+        );
+        assert_eq!(
+            graph.get_upstream("gold.orders", Some(1)),
+            vec!["bronze.users"]
+        );
+        assert!(graph.get_upstream("gold.orders", Some(0)).is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_transitive_paths() -> Result<()> {
+        let mut graph = DependencyGraph::new();
+
+        // gold.report -> gold.orders -> silver.customers -> bronze.users
+        // gold.report -> bronze.users
+        graph.add_dependency("gold.report", "gold.orders")?;
+        graph.add_dependency("gold.orders", "silver.customers")?;
+        graph.add_dependency("silver.customers", "bronze.users")?;
+        graph.add_dependency("gold.report", "bronze.users")?;
+
+        // Grandparents are reached, each along its shortest path
+        let upstream = graph.get_upstream_paths("gold.report", None);
+        let found: Vec<(&str, usize, &[String])> = upstream
+            .iter()
+            .map(|path| (path.model.as_str(), path.depth(), path.via()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("bronze.users", 1, &[][..]),
+                ("gold.orders", 1, &[][..]),
+                ("silver.customers", 2, &["gold.orders".to_string()][..]),
+            ]
+        );
+
+        let downstream = graph.get_downstream_paths("silver.customers", Some(2));
+        assert_eq!(downstream.len(), 2);
+        assert_eq!(
+            downstream[1].path,
+            vec!["silver.customers", "gold.orders", "gold.report"]
+        );
+
+        assert!(graph.get_upstream_paths("missing.model", None).is_empty());
+
         Ok(())
     }
 }
@@ -1,11 +1,13 @@
 use color_eyre::Result;
 use color_eyre::eyre::{Context, eyre};
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
 use std::fs;
 use std::path::{Path, PathBuf};
 
-use crate::dependency_graph::{DependencyGraph, ModelIdentity};
-use crate::parser::{ModelMetadata, ModelParser};
+use crate::config::ProjectConfig;
+use crate::dependency_graph::{DependencyGraph, MAX_REPORTED_CYCLES, ModelIdentity};
+use crate::execution::materialization::Relation;
+use crate::parser::{Materialization, ModelMetadata, ModelParser};
 
 /// Recursively find all SQL files in a directory
 fn find_sql_files(dir: &Path) -> Result<Vec<PathBuf>> {
@@ -45,6 +47,12 @@ pub struct ModelCatalog {
     pub dependency_graph: DependencyGraph,
     /// Directory where models were discovered
     pub model_dir: PathBuf,
+    /// Project configuration supplying defaults for discovered models
+    pub config: ProjectConfig,
+    /// Prefix of the target added to every schema models are built in
+    pub schema_prefix: Option<String>,
+    /// Database relations are qualified with, unless their schema folder configures one
+    pub database: Option<String>,
 }
 
 impl ModelCatalog {
@@ -55,9 +63,55 @@ impl ModelCatalog {
             model_identities: HashMap::new(),
             dependency_graph: DependencyGraph::new(),
             model_dir,
+            config: ProjectConfig::default(),
+            schema_prefix: None,
+            database: None,
         }
     }
 
+    /// Use the given project configuration for model defaults and variables
+    pub fn with_config(mut self, config: ProjectConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Build models in schemas prefixed with the given prefix, e.g. `dev_alice_bronze`
+    pub fn with_schema_prefix(mut self, schema_prefix: Option<String>) -> Self {
+        self.schema_prefix = schema_prefix;
+        self
+    }
+
+    /// Qualify relations with the given database
+    pub fn with_database(mut self, database: Option<String>) -> Self {
+        self.database = database;
+        self
+    }
+
+    /// Catalog of the given models as if they were found at
+    /// `models/<schema>/<table>.sql`, without reading any files. The
+    /// dependency graph is left to `build_dependency_graph`.
+    #[cfg(test)]
+    pub fn from_models(
+        config: ProjectConfig,
+        models: impl IntoIterator<Item = ModelMetadata>,
+    ) -> Result<Self> {
+        let model_dir = PathBuf::from("models");
+        let mut catalog = Self::new(model_dir.clone()).with_config(config);
+        for model in models {
+            let (schema, table) = model
+                .name
+                .split_once('.')
+                .ok_or_else(|| eyre!("Model name {} is not qualified", model.name))?;
+            let file_path = model_dir.join(schema).join(format!("{}.sql", table));
+            catalog.model_identities.insert(
+                model.name.clone(),
+                ModelIdentity::from_path(file_path, &model_dir)?,
+            );
+            catalog.models.insert(model.name.clone(), model);
+        }
+        Ok(catalog)
+    }
+
     /// Discover all SQL models in the specified directory
     pub fn discover_models(&mut self) -> Result<()> {
         // Check if the directory exists
@@ -85,8 +139,7 @@ impl ModelCatalog {
         let model_identity = ModelIdentity::from_path(file_path.to_path_buf(), &self.model_dir)?;
 
         // Read the SQL file content
-        let sql_content = fs::read_to_string(file_path)
-            .wrap_err_with(|| format!("Failed to read SQL file: {:?}", file_path))?;
+        let sql_content = self.read_model_sql(&model_identity)?;
 
         // Create and parse the model using the qualified name
         let mut model = ModelMetadata::new(model_identity.qualified_name.clone());
@@ -107,6 +160,20 @@ impl ModelCatalog {
         Ok(())
     }
 
+    /// Read a model's SQL with project variables substituted
+    pub fn read_model_sql(&self, model_identity: &ModelIdentity) -> Result<String> {
+        let sql_content = fs::read_to_string(&model_identity.file_path).wrap_err_with(|| {
+            format!("Failed to read SQL file: {:?}", model_identity.file_path)
+        })?;
+
+        self.config.substitute_vars(&sql_content).wrap_err_with(|| {
+            format!(
+                "Failed to substitute variables in model {}",
+                model_identity.qualified_name
+            )
+        })
+    }
+
     /// Build a dependency graph from the discovered models
     pub fn build_dependency_graph(&mut self) -> Result<()> {
         // Clear the existing graph
@@ -114,17 +181,28 @@ impl ModelCatalog {
 
         // Add all models to the graph first
         for qualified_name in self.models.keys() {
-            self.dependency_graph.add_model(qualified_name);
+            if self.get_materialization(qualified_name) == Materialization::Ephemeral {
+                self.dependency_graph.mark_ephemeral(qualified_name);
+            } else {
+                self.dependency_graph.add_model(qualified_name);
+            }
         }
 
-        // Add dependencies based on model sources
+        // Add dependencies based on model sources and the references nested in subqueries
         for (model_name, model) in &self.models {
-            for source in &model.sources {
+            let source_ids: BTreeSet<&String> = model
+                .sources
+                .iter()
+                .map(|source| &source.id)
+                .chain(model.references.iter().map(|reference| &reference.source_id))
+                .collect();
+
+            for source_id in source_ids {
                 // Check if the source is another model in our catalog
-                if self.models.contains_key(&source.id) {
-                    // Add dependency: model_name depends on source.id
+                if let Some(dependency) = self.resolve_reference(model_name, source_id)? {
+                    // Add dependency: model_name depends on the resolved model
                     self.dependency_graph
-                        .add_dependency(model_name, &source.id)?;
+                        .add_dependency(model_name, &dependency)?;
                 }
                 // Note: We ignore external dependencies (sources not in our catalog)
                 // as they represent external tables/views
@@ -134,6 +212,60 @@ impl ModelCatalog {
         Ok(())
     }
 
+    /// Resolve a table reference in a model to the model it selects from, or
+    /// None when it refers to an external table or one of the model's own CTEs.
+    /// Qualified names must match a model exactly. Unqualified names are looked
+    /// up in the model's own schema first, then in every schema of the
+    /// project's search path, where a name found in more than one schema is
+    /// ambiguous. A model never resolves to itself, so `FROM users` in
+    /// gold/users.sql refers to a users model in another schema of the search
+    /// path, or else to an external users table.
+    pub fn resolve_reference(&self, model_name: &str, source_id: &str) -> Result<Option<String>> {
+        if self.models.contains_key(source_id) {
+            return Ok(Some(source_id.to_string()));
+        }
+        if source_id.contains('.') {
+            return Ok(None);
+        }
+
+        if self
+            .models
+            .get(model_name)
+            .is_some_and(|model| model.is_cte(source_id))
+        {
+            return Ok(None);
+        }
+
+        let is_candidate =
+            |candidate: &String| candidate != model_name && self.models.contains_key(candidate);
+
+        if let Some(identity) = self.model_identities.get(model_name) {
+            let candidate = format!("{}.{}", identity.schema_name, source_id);
+            if is_candidate(&candidate) {
+                return Ok(Some(candidate));
+            }
+        }
+
+        let candidates: Vec<String> = self
+            .config
+            .project
+            .search_path
+            .iter()
+            .map(|schema| format!("{}.{}", schema, source_id))
+            .filter(is_candidate)
+            .collect();
+        match candidates.as_slice() {
+            [] => Ok(None),
+            [candidate] => Ok(Some(candidate.clone())),
+            _ => Err(eyre!(
+                "Ambiguous reference '{}' in {}: it could be any of {}. Qualify it with the schema of the model it selects from",
+                source_id,
+                model_name,
+                candidates.join(", ")
+            )),
+        }
+    }
+
     /// Get the execution order of all models
     pub fn get_execution_order(&self) -> Result<Vec<String>> {
         self.dependency_graph.execution_order()
@@ -144,13 +276,148 @@ impl ModelCatalog {
         self.dependency_graph.has_cycles()
     }
 
+    /// Describe the circular dependencies, one line per cycle followed by the
+    /// file and line where each model in it selects from the next, e.g.
+    ///
+    /// ```text
+    /// bronze.a -> bronze.b -> bronze.a
+    ///   bronze.a selects from bronze.b at models/bronze/a.sql:3
+    ///   bronze.b selects from bronze.a at models/bronze/b.sql:1
+    /// ```
+    pub fn describe_cycles(&self) -> String {
+        let mut lines = Vec::new();
+        let (cycles, truncated) = self.dependency_graph.reported_cycles();
+        for cycle in cycles {
+            lines.push(cycle.join(" -> "));
+            for edge in cycle.windows(2) {
+                let (model, dependency) = (&edge[0], &edge[1]);
+                let mut location = self
+                    .model_identities
+                    .get(model)
+                    .map(|identity| identity.file_path.display().to_string())
+                    .unwrap_or_else(|| model.clone());
+                let line = self.models.get(model).and_then(|metadata| {
+                    metadata
+                        .references
+                        .iter()
+                        .filter(|reference| {
+                            matches!(
+                                self.resolve_reference(model, &reference.source_id),
+                                Ok(Some(resolved)) if &resolved == dependency
+                            )
+                        })
+                        .map(|reference| reference.line)
+                        .min()
+                });
+                if let Some(line) = line {
+                    location = format!("{}:{}", location, line);
+                }
+                lines.push(format!(
+                    "  {} selects from {} at {}",
+                    model, dependency, location
+                ));
+            }
+        }
+        if truncated {
+            lines.push(format!(
+                "...and more, only the first {} cycles are listed",
+                MAX_REPORTED_CYCLES
+            ));
+        }
+        lines.join("\n")
+    }
+
     /// Get all models that depend on the given model
     pub fn get_dependents(&self, model_name: &str) -> Vec<String> {
         self.dependency_graph.get_dependents(model_name)
     }
 
+    /// Get the view models built on the given model, directly or through other
+    /// views and ephemeral models. Replacing the model's relation has to drop
+    /// them first, so they are rebuilt after it.
+    pub fn get_dependent_views(&self, model_name: &str) -> Vec<String> {
+        let mut views = BTreeSet::new();
+        let mut visited = HashSet::from([model_name.to_string()]);
+        let mut queue = VecDeque::from([model_name.to_string()]);
+        while let Some(current) = queue.pop_front() {
+            for dependent in self.get_dependents(&current) {
+                if !visited.insert(dependent.clone()) {
+                    continue;
+                }
+                match self.get_materialization(&dependent) {
+                    Materialization::View => {
+                        views.insert(dependent.clone());
+                        queue.push_back(dependent);
+                    }
+                    // Ephemeral models are inlined, their dependents select from the model directly
+                    Materialization::Ephemeral => queue.push_back(dependent),
+                    _ => {}
+                }
+            }
+        }
+        views.into_iter().collect()
+    }
+
     /// Get all models that the given model depends on
     pub fn get_dependencies(&self, model_name: &str) -> Vec<String> {
         self.dependency_graph.get_dependencies(model_name)
     }
+
+    /// Get the materialization of a model: its own directive wins over the
+    /// schema folder default, which wins over the project default
+    pub fn get_materialization(&self, model_name: &str) -> Materialization {
+        self.models
+            .get(model_name)
+            .and_then(|model| model.config.materialized)
+            .unwrap_or_else(|| {
+                let schema = self
+                    .model_identities
+                    .get(model_name)
+                    .map(|identity| identity.schema_name.as_str())
+                    .unwrap_or_default();
+                self.config.default_materialization(schema)
+            })
+    }
+
+    /// Get the tags of a model, including those of its schema folder
+    pub fn get_tags(&self, model_name: &str) -> Vec<String> {
+        let mut tags = Vec::new();
+        if let Some(identity) = self.model_identities.get(model_name) {
+            tags.extend_from_slice(self.config.schema_tags(&identity.schema_name));
+        }
+        if let Some(model) = self.models.get(model_name) {
+            for tag in &model.config.tags {
+                if !tags.contains(tag) {
+                    tags.push(tag.clone());
+                }
+            }
+        }
+        tags
+    }
+
+    /// Get the physical relation a model is built as
+    pub fn relation(&self, model_name: &str) -> Option<Relation> {
+        self.model_identities.get(model_name).map(|identity| {
+            let database = self
+                .config
+                .target_database(&identity.schema_name)
+                .map(|database| database.to_string())
+                .or_else(|| self.database.clone());
+            Relation::new(
+                &self.physical_schema(&identity.schema_name),
+                &identity.table_name,
+            )
+            .with_database(database)
+        })
+    }
+
+    /// Get the database schema that models in a schema folder are built in,
+    /// applying the folder's target schema and then the target's prefix
+    pub fn physical_schema(&self, schema: &str) -> String {
+        let target_schema = self.config.target_schema(schema);
+        match &self.schema_prefix {
+            Some(prefix) => format!("{}_{}", prefix, target_schema),
+            None => target_schema.to_string(),
+        }
+    }
 }
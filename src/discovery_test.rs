@@ -1,10 +1,15 @@
 use color_eyre::Result;
+use std::collections::BTreeSet;
 use std::fs::{self, File};
 use std::io::Write;
 use std::path::Path;
 use tempfile::tempdir;
 
+use crate::compiler::{compile_model, write_compiled_models};
+use crate::config::ProjectConfig;
 use crate::discovery::ModelCatalog;
+use crate::execution::SqlDialect;
+use crate::parser::{Materialization, ModelMetadata};
 
 #[test]
 fn test_discover_models_in_directory() -> Result<()> {
@@ -115,6 +120,56 @@ fn test_discover_models_in_directory() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_model_materialization_defaults() -> Result<()> {
+    let temp_dir = tempdir()?;
+    let model_dir = temp_dir.path().to_path_buf();
+
+    let staging_dir = model_dir.join("staging");
+    fs::create_dir(&staging_dir)?;
+
+    create_test_sql_file(&staging_dir, "users.sql", "SELECT a FROM source1")?;
+    create_test_sql_file(
+        &staging_dir,
+        "orders.sql",
+        "-- cadac: materialized=view\nSELECT b FROM source2",
+    )?;
+
+    let mut catalog = ModelCatalog::new(model_dir);
+    catalog.discover_models()?;
+
+    // Models without a directive use the project default
+    assert_eq!(
+        catalog.get_materialization("staging.users"),
+        Materialization::Table
+    );
+    assert_eq!(
+        catalog.get_materialization("staging.orders"),
+        Materialization::View
+    );
+
+    catalog.config = toml::from_str("[models]\nmaterialized = \"view\"")?;
+    assert_eq!(
+        catalog.get_materialization("staging.users"),
+        Materialization::View
+    );
+
+    // Folder config wins over the project default, file directives win over both
+    catalog.config = toml::from_str(
+        "[models]\nmaterialized = \"view\"\n[schemas.staging]\nmaterialized = \"ephemeral\"",
+    )?;
+    assert_eq!(
+        catalog.get_materialization("staging.users"),
+        Materialization::Ephemeral
+    );
+    assert_eq!(
+        catalog.get_materialization("staging.orders"),
+        Materialization::View
+    );
+
+    Ok(())
+}
+
 #[test]
 fn test_discover_models_nonexistent_directory() -> Result<()> {
     // Try to discover models in a nonexistent directory
@@ -135,3 +190,347 @@ fn create_test_sql_file(dir: &Path, filename: &str, content: &str) -> Result<()>
     writeln!(file, "{}", content)?;
     Ok(())
 }
+
+#[test]
+fn test_ephemeral_models_inlined_as_ctes() -> Result<()> {
+    let temp_dir = tempdir()?;
+    let model_dir = temp_dir.path().to_path_buf();
+    let staging_dir = model_dir.join("staging");
+    let gold_dir = model_dir.join("gold");
+    fs::create_dir(&staging_dir)?;
+    fs::create_dir(&gold_dir)?;
+
+    create_test_sql_file(
+        &staging_dir,
+        "active_users.sql",
+        "-- cadac: materialized=ephemeral\nSELECT id FROM raw_users;",
+    )?;
+    create_test_sql_file(
+        &gold_dir,
+        "orders.sql",
+        "SELECT o.id FROM raw_orders o JOIN staging.active_users u ON o.user_id = u.id",
+    )?;
+
+    let mut catalog = ModelCatalog::new(model_dir);
+    catalog.discover_models()?;
+    catalog.build_dependency_graph()?;
+
+    // The ephemeral model orders its dependents but never runs itself
+    assert_eq!(catalog.get_execution_order()?, vec!["gold.orders"]);
+    assert_eq!(catalog.get_dependencies("gold.orders"), vec!["staging.active_users"]);
+
+    let compiled = compile_model(&catalog, "gold.orders")?;
+    assert_eq!(
+        compiled.trim_end(),
+        "WITH __cadac_ephemeral__staging__active_users AS (\n\
+         -- cadac: materialized=ephemeral\nSELECT id FROM raw_users\n)\n\
+         SELECT o.id FROM raw_orders o JOIN __cadac_ephemeral__staging__active_users u ON o.user_id = u.id"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_schema_prefix_rewrites_model_references() -> Result<()> {
+    let temp_dir = tempdir()?;
+    let model_dir = temp_dir.path().to_path_buf();
+    let bronze_dir = model_dir.join("bronze");
+    let gold_dir = model_dir.join("gold");
+    fs::create_dir(&bronze_dir)?;
+    fs::create_dir(&gold_dir)?;
+
+    create_test_sql_file(&bronze_dir, "users.sql", "SELECT id FROM raw_users")?;
+    create_test_sql_file(
+        &gold_dir,
+        "orders.sql",
+        "SELECT o.id FROM raw_orders o JOIN bronze.users u ON o.user_id = u.id",
+    )?;
+
+    let mut catalog =
+        ModelCatalog::new(model_dir).with_schema_prefix(Some("dev_alice".to_string()));
+    catalog.discover_models()?;
+    catalog.build_dependency_graph()?;
+
+    assert_eq!(
+        catalog.relation("bronze.users").unwrap().to_string(),
+        "dev_alice_bronze.users"
+    );
+
+    // References to models follow them into the prefixed schema, sources don't
+    let compiled = compile_model(&catalog, "gold.orders")?;
+    assert_eq!(
+        compiled.trim_end(),
+        "SELECT o.id FROM raw_orders o JOIN dev_alice_bronze.users u ON o.user_id = u.id"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_compile_rewrites_nested_model_references() -> Result<()> {
+    let temp_dir = tempdir()?;
+    let model_dir = temp_dir.path().to_path_buf();
+    let bronze_dir = model_dir.join("bronze");
+    let gold_dir = model_dir.join("gold");
+    fs::create_dir(&bronze_dir)?;
+    fs::create_dir(&gold_dir)?;
+
+    create_test_sql_file(&bronze_dir, "Users.sql", "SELECT id FROM raw_users")?;
+    create_test_sql_file(
+        &gold_dir,
+        "active_users.sql",
+        "-- Users with orders\nSELECT u.id\nFROM (SELECT id FROM bronze.Users) u -- all users\nWHERE u.id IN (SELECT user_id FROM raw_orders)",
+    )?;
+
+    let mut catalog =
+        ModelCatalog::new(model_dir).with_database(Some("analytics".to_string()));
+    catalog.discover_models()?;
+    catalog.build_dependency_graph()?;
+
+    // References in subqueries still create dependencies
+    assert_eq!(
+        catalog.get_dependencies("gold.active_users"),
+        vec!["bronze.Users"]
+    );
+
+    // Only the model reference changes, comments and formatting are preserved
+    let compiled = compile_model(&catalog, "gold.active_users")?;
+    assert_eq!(
+        compiled.trim_end(),
+        "-- Users with orders\nSELECT u.id\nFROM (SELECT id FROM analytics.bronze.\"Users\") u -- all users\nWHERE u.id IN (SELECT user_id FROM raw_orders)"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_circular_dependencies_are_described() -> Result<()> {
+    let temp_dir = tempdir()?;
+    let model_dir = temp_dir.path().to_path_buf();
+    let silver_dir = model_dir.join("silver");
+    fs::create_dir(&silver_dir)?;
+
+    create_test_sql_file(
+        &silver_dir,
+        "customers.sql",
+        "SELECT id\nFROM silver.orders",
+    )?;
+    create_test_sql_file(
+        &silver_dir,
+        "orders.sql",
+        "SELECT o.id\nFROM raw_orders o\nJOIN silver.customers c ON o.customer_id = c.id",
+    )?;
+
+    let mut catalog = ModelCatalog::new(model_dir.clone());
+    catalog.discover_models()?;
+    catalog.build_dependency_graph()?;
+
+    assert!(catalog.has_circular_dependencies());
+    let error = catalog.get_execution_order().unwrap_err().to_string();
+    assert!(error.contains("silver.customers -> silver.orders -> silver.customers"));
+
+    // Each step of the cycle points at the file and line of the reference
+    let customers = silver_dir.join("customers.sql");
+    let orders = silver_dir.join("orders.sql");
+    assert_eq!(
+        catalog.describe_cycles(),
+        format!(
+            "silver.customers -> silver.orders -> silver.customers\n  \
+             silver.customers selects from silver.orders at {}:2\n  \
+             silver.orders selects from silver.customers at {}:3",
+            customers.display(),
+            orders.display()
+        )
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_resolve_unqualified_references() -> Result<()> {
+    let mut config = ProjectConfig::default();
+    config.project.search_path = vec!["staging".to_string(), "bronze".to_string()];
+    let models = [
+        ("bronze.users", vec!["users"]),
+        ("staging.users", vec![]),
+        ("staging.payments", vec![]),
+        ("staging.events", vec![]),
+        ("gold.orders", vec!["users", "events", "raw_orders"]),
+        ("gold.revenue", vec!["payments"]),
+    ]
+    .map(|(name, source_ids)| ModelMetadata::selecting_from(name, &source_ids));
+    let mut catalog = ModelCatalog::from_models(config, models)?;
+
+    // The model's own schema wins over the search path
+    assert_eq!(
+        catalog.resolve_reference("staging.events", "users")?,
+        Some("staging.users".to_string())
+    );
+    // A model never resolves to itself, so bronze.users reads staging.users
+    assert_eq!(
+        catalog.resolve_reference("bronze.users", "users")?,
+        Some("staging.users".to_string())
+    );
+    // Qualified names only match exactly
+    assert_eq!(catalog.resolve_reference("gold.orders", "raw.users")?, None);
+    assert_eq!(
+        catalog.resolve_reference("gold.orders", "events")?,
+        Some("staging.events".to_string())
+    );
+    assert_eq!(catalog.resolve_reference("gold.orders", "raw_orders")?, None);
+
+    // Names found in more than one search path schema are ambiguous
+    let error = catalog.build_dependency_graph().unwrap_err().to_string();
+    assert!(error.contains("Ambiguous reference 'users' in gold.orders"));
+    assert!(error.contains("staging.users, bronze.users"));
+
+    catalog.models.remove("bronze.users");
+    catalog.build_dependency_graph()?;
+    assert_eq!(
+        catalog.get_dependencies("gold.orders").into_iter().collect::<BTreeSet<_>>(),
+        BTreeSet::from(["staging.events".to_string(), "staging.users".to_string()])
+    );
+    assert_eq!(catalog.get_dependencies("gold.revenue"), vec!["staging.payments"]);
+
+    // Names bound by the model's own WITH clause are never resolved to models
+    catalog.models.get_mut("gold.revenue").unwrap().cte_names = vec!["payments".to_string()];
+    assert_eq!(catalog.resolve_reference("gold.revenue", "payments")?, None);
+    assert_eq!(
+        catalog.resolve_reference("gold.orders", "payments")?,
+        Some("staging.payments".to_string())
+    );
+    catalog.build_dependency_graph()?;
+    assert!(catalog.get_dependencies("gold.revenue").is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_resolve_table_name_shared_across_schemas() -> Result<()> {
+    let mut config = ProjectConfig::default();
+    config.project.search_path = vec!["bronze".to_string()];
+    let models = [
+        ModelMetadata::selecting_from("bronze.users", &["users"]),
+        ModelMetadata::selecting_from("gold.users", &["users"]),
+    ];
+    let mut catalog = ModelCatalog::from_models(config, models)?;
+    catalog.build_dependency_graph()?;
+
+    // gold/users.sql reads the bronze.users model through the search path, while
+    // bronze/users.sql, the only model of that name there, reads an external table
+    assert_eq!(catalog.get_dependencies("gold.users"), vec!["bronze.users"]);
+    assert!(catalog.get_dependencies("bronze.users").is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_dependent_views() -> Result<()> {
+    let models = [
+        ("bronze.users", vec![], Materialization::Table),
+        ("silver.customers", vec!["bronze.users"], Materialization::View),
+        ("silver.active", vec!["bronze.users"], Materialization::Ephemeral),
+        ("gold.active_customers", vec!["silver.active"], Materialization::View),
+        ("gold.summary", vec!["silver.customers"], Materialization::View),
+        ("gold.orders", vec!["silver.customers"], Materialization::Table),
+        ("gold.report", vec!["gold.orders"], Materialization::View),
+    ]
+    .map(|(name, source_ids, materialized)| {
+        let mut model = ModelMetadata::selecting_from(name, &source_ids);
+        model.config.materialized = Some(materialized);
+        model
+    });
+    let mut catalog = ModelCatalog::from_models(ProjectConfig::default(), models)?;
+    catalog.build_dependency_graph()?;
+
+    // Views on views and views through ephemeral models, but nothing past a table
+    assert_eq!(
+        catalog.get_dependent_views("bronze.users"),
+        vec!["gold.active_customers", "gold.summary", "silver.customers"]
+    );
+    assert_eq!(catalog.get_dependent_views("gold.orders"), vec!["gold.report"]);
+    assert!(catalog.get_dependent_views("gold.report").is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_compile_rewrites_unqualified_model_references() -> Result<()> {
+    let temp_dir = tempdir()?;
+    let model_dir = temp_dir.path().to_path_buf();
+    let bronze_dir = model_dir.join("bronze");
+    fs::create_dir(&bronze_dir)?;
+
+    create_test_sql_file(&bronze_dir, "users.sql", "SELECT id FROM users")?;
+    create_test_sql_file(
+        &bronze_dir,
+        "active_users.sql",
+        "SELECT u.id FROM users u WHERE u.active",
+    )?;
+
+    let mut catalog =
+        ModelCatalog::new(model_dir).with_schema_prefix(Some("dev_alice".to_string()));
+    catalog.discover_models()?;
+    catalog.build_dependency_graph()?;
+
+    // users in bronze/active_users.sql is the bronze.users model, but in
+    // bronze/users.sql itself it is the external users table
+    assert_eq!(catalog.get_dependencies("bronze.active_users"), vec!["bronze.users"]);
+    assert!(catalog.get_dependencies("bronze.users").is_empty());
+
+    let compiled = compile_model(&catalog, "bronze.active_users")?;
+    assert_eq!(
+        compiled.trim_end(),
+        "SELECT u.id FROM dev_alice_bronze.users u WHERE u.active"
+    );
+    assert_eq!(
+        compile_model(&catalog, "bronze.users")?.trim_end(),
+        "SELECT id FROM users"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_write_compiled_models() -> Result<()> {
+    let temp_dir = tempdir()?;
+    let model_dir = temp_dir.path().join("models");
+    let staging_dir = model_dir.join("staging");
+    let gold_dir = model_dir.join("gold");
+    fs::create_dir_all(&staging_dir)?;
+    fs::create_dir_all(&gold_dir)?;
+
+    create_test_sql_file(
+        &staging_dir,
+        "users.sql",
+        "-- cadac: materialized=ephemeral\nSELECT id FROM raw_users",
+    )?;
+    create_test_sql_file(
+        &gold_dir,
+        "orders.sql",
+        "SELECT o.id FROM raw_orders o JOIN staging.users u ON o.user_id = u.id",
+    )?;
+
+    let mut catalog = ModelCatalog::new(model_dir).with_schema_prefix(Some("dev".to_string()));
+    catalog.discover_models()?;
+    catalog.build_dependency_graph()?;
+
+    let output_dir = temp_dir.path().join("target/compiled");
+    fs::create_dir_all(output_dir.join("staging"))?;
+    fs::write(output_dir.join("staging/users.sql"), "SELECT 1")?;
+
+    // Only the selected models' output is replaced
+    let selected = BTreeSet::from(["gold.orders".to_string()]);
+    let written = write_compiled_models(&catalog, &selected, &SqlDialect::Postgres, &output_dir)?;
+    assert_eq!(written, vec![output_dir.join("gold/orders.sql")]);
+    assert_eq!(fs::read_to_string(output_dir.join("staging/users.sql"))?, "SELECT 1");
+
+    let orders = fs::read_to_string(output_dir.join("gold/orders.sql"))?;
+    assert!(orders.starts_with("CREATE SCHEMA IF NOT EXISTS dev_gold;\n"));
+    assert!(orders.contains(
+        "CREATE TABLE dev_gold.orders__cadac_tmp AS\nWITH __cadac_ephemeral__staging__users AS ("
+    ));
+    assert!(orders.ends_with("ALTER TABLE dev_gold.orders__cadac_tmp RENAME TO orders;\n"));
+
+    Ok(())
+}
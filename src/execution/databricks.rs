@@ -1,7 +1,10 @@
 // Databricks adapter implementation
 // This will be implemented when databricks feature is added
 
-use super::{DatabaseAdapter, DatabaseConnection, ExecutionResult, ExecutionStatus, SqlDialect};
+use super::materialization::{BuildStatements, Relation, RelationType};
+use super::{
+    DatabaseAdapter, DatabaseConnection, ExecutionErrorKind, ErrorDetails, ExecutionResult, ExecutionStatus, SqlDialect,
+};
 use color_eyre::Result;
 
 /// Databricks connection implementation (placeholder)
@@ -14,6 +17,26 @@ impl DatabaseConnection for DatabricksConnection {
         unimplemented!("Databricks adapter not yet implemented")
     }
 
+    async fn execute_statements(&mut self, _statements: &BuildStatements) -> Result<ExecutionResult> {
+        // TODO: Implement Databricks statement execution
+        unimplemented!("Databricks adapter not yet implemented")
+    }
+
+    async fn get_relation_type(&self, _relation: &Relation) -> Result<Option<RelationType>> {
+        // TODO: Implement Databricks relation lookup
+        unimplemented!("Databricks adapter not yet implemented")
+    }
+
+    async fn get_columns(&self, _relation: &Relation) -> Result<Vec<String>> {
+        // TODO: Implement Databricks column lookup
+        unimplemented!("Databricks adapter not yet implemented")
+    }
+
+    fn categorize_error(&self, error: &color_eyre::Report) -> ErrorDetails {
+        // TODO: Categorize Databricks errors
+        ErrorDetails::new(ExecutionErrorKind::Unknown, error.to_string())
+    }
+
     fn dialect(&self) -> SqlDialect {
         SqlDialect::Databricks
     }
@@ -33,6 +56,11 @@ impl DatabaseAdapter for DatabricksAdapter {
         unimplemented!("Databricks adapter not yet implemented")
     }
 
+    fn categorize_error(&self, error: &color_eyre::Report) -> ErrorDetails {
+        // TODO: Categorize Databricks connection errors
+        ErrorDetails::new(ExecutionErrorKind::Unknown, error.to_string())
+    }
+
     fn dialect(&self) -> SqlDialect {
         SqlDialect::Databricks
     }
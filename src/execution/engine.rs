@@ -0,0 +1,717 @@
+use super::SqlDialect;
+use super::materialization::{self, BuildStatements, ModelBuild, Relation, RelationType};
+use crate::parser::Materialization;
+use color_eyre::Result;
+use serde::{Serialize, Serializer};
+use pool::ConnectionPool;
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::pin::pin;
+use std::sync::{Arc, Mutex};
+use std::task::Poll;
+use std::time::{Duration, SystemTime};
+
+/// Represents the result of executing a SQL statement
+#[derive(Debug, Clone, Serialize)]
+pub struct ExecutionResult {
+    pub rows_affected: u64,
+    /// Serialized in seconds
+    #[serde(serialize_with = "serialize_seconds")]
+    pub execution_time: Duration,
+    pub status: ExecutionStatus,
+    pub message: Option<String>,
+    /// Serialized as an RFC 3339 timestamp
+    #[serde(serialize_with = "serialize_timestamp")]
+    pub started_at: SystemTime,
+    pub query_hash: Option<String>,
+    /// What went wrong when execution failed
+    pub error: Option<ErrorDetails>,
+    /// Number of times execution was attempted, including retries
+    pub attempts: u32,
+}
+
+impl ExecutionResult {
+    pub fn new(status: ExecutionStatus) -> Self {
+        Self {
+            rows_affected: 0,
+            execution_time: Duration::from_millis(0),
+            status,
+            message: None,
+            started_at: SystemTime::now(),
+            query_hash: None,
+            error: None,
+            attempts: 1,
+        }
+    }
+
+    pub fn with_message(mut self, message: String) -> Self {
+        self.message = Some(message);
+        self
+    }
+
+    pub fn with_rows_affected(mut self, rows: u64) -> Self {
+        self.rows_affected = rows;
+        self
+    }
+
+    pub fn with_execution_time(mut self, duration: Duration) -> Self {
+        self.execution_time = duration;
+        self
+    }
+
+    pub fn with_started_at(mut self, started_at: SystemTime) -> Self {
+        self.started_at = started_at;
+        self
+    }
+
+    pub fn with_query_hash(mut self, hash: String) -> Self {
+        self.query_hash = Some(hash);
+        self
+    }
+
+    pub fn with_error(mut self, error: ErrorDetails) -> Self {
+        self.error = Some(error);
+        self
+    }
+
+    pub fn with_attempts(mut self, attempts: u32) -> Self {
+        self.attempts = attempts;
+        self
+    }
+
+    /// Whether execution failed with an error that may succeed when retried
+    pub fn is_recoverable_failure(&self) -> bool {
+        self.status == ExecutionStatus::Failed
+            && self.error.as_ref().is_some_and(|error| error.kind.is_recoverable())
+    }
+}
+
+/// Status of SQL execution
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExecutionStatus {
+    Success,
+    Failed,
+    Skipped,
+}
+
+/// Kind of error a statement failed with, shared by every adapter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecutionErrorKind {
+    Syntax,
+    MissingRelation,
+    MissingColumn,
+    /// Any other statement the database rejects, e.g. an unknown function or type mismatch
+    InvalidStatement,
+    PermissionDenied,
+    DuplicateKey,
+    ConstraintViolation,
+    /// A relation can't be dropped because objects outside the project depend on it
+    DependentObjects,
+    DataException,
+    /// Serialization failure or deadlock between concurrent transactions
+    Serialization,
+    InsufficientResources,
+    Connection,
+    /// The statement was cancelled, by the server's statement_timeout or
+    /// because the model ran past its own timeout
+    Timeout,
+    /// A lock wait ran past the server's lock_timeout
+    LockTimeout,
+    /// The run was cancelled while the statement was executing
+    Cancelled,
+    Unknown,
+}
+
+impl ExecutionErrorKind {
+    /// Classify a SQLSTATE code, falling back to its two-character class
+    pub fn from_sqlstate(code: &str) -> Self {
+        match code {
+            "42601" => ExecutionErrorKind::Syntax,
+            "42P01" | "3F000" => ExecutionErrorKind::MissingRelation,
+            "42703" => ExecutionErrorKind::MissingColumn,
+            "42501" => ExecutionErrorKind::PermissionDenied,
+            "23505" => ExecutionErrorKind::DuplicateKey,
+            "2BP01" => ExecutionErrorKind::DependentObjects,
+            "40001" | "40P01" => ExecutionErrorKind::Serialization,
+            "57014" => ExecutionErrorKind::Timeout,
+            "55P03" => ExecutionErrorKind::LockTimeout,
+            "57P01" | "57P02" | "57P03" => ExecutionErrorKind::Connection,
+            _ => match code.get(..2) {
+                Some("08") => ExecutionErrorKind::Connection,
+                Some("22") => ExecutionErrorKind::DataException,
+                Some("23") => ExecutionErrorKind::ConstraintViolation,
+                Some("28") => ExecutionErrorKind::PermissionDenied,
+                Some("40") => ExecutionErrorKind::Serialization,
+                Some("42") => ExecutionErrorKind::InvalidStatement,
+                Some("53") => ExecutionErrorKind::InsufficientResources,
+                _ => ExecutionErrorKind::Unknown,
+            },
+        }
+    }
+
+    /// Whether the error is transient, so running the model again may succeed.
+    /// Timeouts are not: the model would most likely run just as long again.
+    pub fn is_recoverable(self) -> bool {
+        matches!(
+            self,
+            ExecutionErrorKind::Connection
+                | ExecutionErrorKind::LockTimeout
+                | ExecutionErrorKind::Serialization
+                | ExecutionErrorKind::InsufficientResources
+        )
+    }
+}
+
+impl fmt::Display for ExecutionErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ExecutionErrorKind::Syntax => "SYNTAX_ERROR",
+            ExecutionErrorKind::MissingRelation => "MISSING_RELATION",
+            ExecutionErrorKind::MissingColumn => "MISSING_COLUMN",
+            ExecutionErrorKind::InvalidStatement => "INVALID_STATEMENT",
+            ExecutionErrorKind::PermissionDenied => "PERMISSION_DENIED",
+            ExecutionErrorKind::DuplicateKey => "DUPLICATE_KEY",
+            ExecutionErrorKind::ConstraintViolation => "CONSTRAINT_VIOLATION",
+            ExecutionErrorKind::DependentObjects => "DEPENDENT_OBJECTS",
+            ExecutionErrorKind::DataException => "DATA_EXCEPTION",
+            ExecutionErrorKind::Serialization => "SERIALIZATION_FAILURE",
+            ExecutionErrorKind::InsufficientResources => "INSUFFICIENT_RESOURCES",
+            ExecutionErrorKind::Connection => "CONNECTION_ERROR",
+            ExecutionErrorKind::Timeout => "TIMEOUT",
+            ExecutionErrorKind::LockTimeout => "LOCK_TIMEOUT",
+            ExecutionErrorKind::Cancelled => "CANCELLED",
+            ExecutionErrorKind::Unknown => "UNKNOWN_ERROR",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Serialized by its display name, e.g. "SYNTAX_ERROR"
+impl Serialize for ExecutionErrorKind {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// A database error with its kind and whatever the database reported about it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ErrorDetails {
+    pub kind: ExecutionErrorKind,
+    /// SQLSTATE code reported by the database
+    pub code: Option<String>,
+    pub message: String,
+    pub detail: Option<String>,
+    pub hint: Option<String>,
+    /// 1-based character position of the error in the failed statement
+    pub position: Option<usize>,
+    /// Index of the failed statement among the statements executed together
+    pub statement_index: Option<usize>,
+    /// 1-based line of the compiled model SQL the error points at
+    pub model_line: Option<usize>,
+}
+
+impl ErrorDetails {
+    pub fn new(kind: ExecutionErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            code: None,
+            message: message.into(),
+            detail: None,
+            hint: None,
+            position: None,
+            statement_index: None,
+            model_line: None,
+        }
+    }
+}
+
+impl fmt::Display for ErrorDetails {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}", self.kind, self.message)?;
+        if let Some(code) = &self.code {
+            write!(f, " (SQLSTATE {})", code)?;
+        }
+        if let Some(line) = self.model_line {
+            write!(f, " at model line {}", line)?;
+        }
+        if let Some(detail) = &self.detail {
+            write!(f, "\n      Detail: {}", detail)?;
+        }
+        if let Some(hint) = &self.hint {
+            write!(f, "\n      Hint: {}", hint)?;
+        }
+        Ok(())
+    }
+}
+
+/// Why a running model was interrupted before it finished
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interruption {
+    /// The model ran longer than its timeout
+    Timeout(Duration),
+    /// The run was cancelled, e.g. with Ctrl-C
+    Cancelled,
+}
+
+impl Interruption {
+    /// Describe the interruption as the error the model failed with
+    pub fn error(self) -> ErrorDetails {
+        match self {
+            Interruption::Timeout(timeout) => {
+                ErrorDetails::new(ExecutionErrorKind::Timeout, format!("Model exceeded its timeout of {:?}", timeout))
+            }
+            Interruption::Cancelled => ErrorDetails::new(ExecutionErrorKind::Cancelled, "Model was cancelled"),
+        }
+    }
+}
+
+/// How models that fail with a recoverable error are retried
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Number of retries after the first attempt
+    pub retries: u32,
+    /// Delay before the first retry, doubled for every retry after it
+    pub initial_backoff: Duration,
+    /// Upper bound on the delay between retries
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Delay before the given retry, counting from 1
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        self.initial_backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retries: 0,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Database connection trait for abstracting different database types
+#[async_trait::async_trait]
+pub trait DatabaseConnection: Send + Sync {
+    async fn execute_sql(&self, sql: &str) -> Result<ExecutionResult>;
+    /// Execute several statements as a single atomic unit, returning a summary result
+    /// with the rows affected by the counted statements
+    async fn execute_statements(&mut self, statements: &BuildStatements) -> Result<ExecutionResult>;
+    /// Look up the kind of relation that currently exists, if any
+    async fn get_relation_type(&self, relation: &Relation) -> Result<Option<RelationType>>;
+    /// List the column names of an existing relation, in table order
+    async fn get_columns(&self, relation: &Relation) -> Result<Vec<String>>;
+    /// Classify an error returned by one of the connection's methods
+    fn categorize_error(&self, error: &color_eyre::Report) -> ErrorDetails;
+    /// Check that the connection still works before it is reused
+    async fn is_healthy(&self) -> bool {
+        true
+    }
+    /// Handle for cancelling the statement the connection is running on the
+    /// server, if the platform supports it
+    fn canceller(&self) -> Option<Box<dyn QueryCanceller>> {
+        None
+    }
+    fn dialect(&self) -> SqlDialect;
+    async fn close(&self) -> Result<()>;
+}
+
+/// Cancels the statement running on a connection from outside of it
+#[async_trait::async_trait]
+pub trait QueryCanceller: Send + Sync {
+    async fn cancel(&self) -> Result<()>;
+}
+
+/// Database adapter trait for creating connections to different platforms
+#[async_trait::async_trait]
+pub trait DatabaseAdapter: Send + Sync {
+    async fn connect(&self, connection_string: &str) -> Result<Box<dyn DatabaseConnection>>;
+    /// Classify an error returned by `connect`, so that only failures such as
+    /// a lost network connection are retried, not e.g. a wrong password
+    fn categorize_error(&self, error: &color_eyre::Report) -> ErrorDetails;
+    fn dialect(&self) -> SqlDialect;
+    fn validate_connection_string(&self, connection_string: &str) -> Result<()>;
+}
+
+/// Options for running models
+#[derive(Debug, Clone)]
+pub struct RunOptions {
+    /// Selectors choosing the models to run, all models when empty
+    pub select: Vec<String>,
+    /// Selectors choosing models to leave out
+    pub exclude: Vec<String>,
+    pub dry_run: bool,
+    pub fail_fast: bool,
+    pub full_refresh: bool,
+    /// Run snapshot models instead of regular models
+    pub snapshot: bool,
+    /// Run only the models that didn't succeed in the previous run, and their dependents
+    pub retry_failed: bool,
+    pub target_database: Option<String>,
+    /// Maximum number of models run concurrently
+    pub threads: usize,
+    pub retry: RetryPolicy,
+    /// How long a model may run before it is cancelled, unless it sets its own timeout
+    pub timeout: Option<Duration>,
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        Self {
+            select: Vec::new(),
+            exclude: Vec::new(),
+            dry_run: false,
+            fail_fast: true,
+            full_refresh: false,
+            snapshot: false,
+            retry_failed: false,
+            target_database: None,
+            threads: 1,
+            retry: RetryPolicy::default(),
+            timeout: None,
+        }
+    }
+}
+
+/// Execution plan for running models
+#[derive(Debug, Clone)]
+pub struct ExecutionPlan {
+    pub models: Vec<String>,
+    pub execution_order: Vec<String>,
+    pub dry_run: bool,
+}
+
+/// Main execution engine for orchestrating model runs
+pub struct ExecutionEngine {
+    adapters: HashMap<SqlDialect, Arc<dyn DatabaseAdapter>>,
+    /// Connection pools kept for the lifetime of the engine, keyed by dialect and connection string
+    pools: Mutex<HashMap<(SqlDialect, String), Arc<ConnectionPool>>>,
+    pool_size: usize,
+}
+
+impl ExecutionEngine {
+    pub fn new() -> Self {
+        let adapters: HashMap<SqlDialect, Arc<dyn DatabaseAdapter>> = HashMap::new();
+        Self {
+            adapters,
+            pools: Mutex::new(HashMap::new()),
+            pool_size: 1,
+        }
+    }
+
+    /// Register a database adapter for a specific dialect
+    pub fn register_adapter(&mut self, dialect: SqlDialect, adapter: Box<dyn DatabaseAdapter>) {
+        self.adapters.insert(dialect, Arc::from(adapter));
+    }
+
+    /// Set how many idle connections each pool keeps, typically the number of threads
+    pub fn set_pool_size(&mut self, size: usize) {
+        self.pool_size = size.max(1);
+    }
+
+    /// Get list of available database dialects
+    pub fn available_dialects(&self) -> Vec<SqlDialect> {
+        self.adapters.keys().cloned().collect()
+    }
+
+    /// Check if a dialect is supported
+    pub fn supports_dialect(&self, dialect: &SqlDialect) -> bool {
+        self.adapters.contains_key(dialect)
+    }
+
+    /// Look up the adapter registered for a dialect
+    pub fn adapter(&self, dialect: &SqlDialect) -> Result<&dyn DatabaseAdapter> {
+        self.adapters.get(dialect)
+            .map(|adapter| adapter.as_ref())
+            .ok_or_else(|| color_eyre::eyre::eyre!(
+                "No adapter found for dialect: {:?}. Available dialects: {:?}", 
+                dialect, 
+                self.available_dialects()
+            ))
+    }
+
+    /// Get the connection pool for a database, creating it on first use
+    pub fn pool(&self, connection_string: &str, dialect: &SqlDialect) -> Result<Arc<ConnectionPool>> {
+        let adapter = self.adapters.get(dialect)
+            .ok_or_else(|| color_eyre::eyre::eyre!("No adapter found for dialect: {:?}", dialect))?;
+        adapter.validate_connection_string(connection_string)?;
+
+        let mut pools = self.pools.lock().unwrap();
+        let pool = pools
+            .entry((dialect.clone(), connection_string.to_string()))
+            .or_insert_with(|| Arc::new(ConnectionPool::new(adapter.clone(), connection_string, self.pool_size)));
+        Ok(pool.clone())
+    }
+
+    /// Close the idle connections of every pool
+    pub async fn close_connections(&self) -> Result<()> {
+        let pools: Vec<Arc<ConnectionPool>> = self.pools.lock().unwrap().drain().map(|(_, pool)| pool).collect();
+        for pool in pools {
+            pool.close().await?;
+        }
+        Ok(())
+    }
+
+    /// Execute SQL using the specified dialect
+    pub async fn execute_sql(
+        &self,
+        sql: &str,
+        connection_string: &str,
+        dialect: SqlDialect,
+    ) -> Result<ExecutionResult> {
+        let pool = self.pool(connection_string, &dialect)?;
+        let connection = pool.get().await?;
+        connection.execute_sql(sql).await
+    }
+
+    /// Materialize a model, replacing whatever relation currently exists at its
+    /// target unless the model is incremental or a snapshot and can be updated in place.
+    /// Its statements are cancelled on the server if `interrupt` completes before they do.
+    pub async fn materialize(
+        &self,
+        build: &ModelBuild,
+        connection_string: &str,
+        dialect: SqlDialect,
+        interrupt: impl Future<Output = Interruption> + Send,
+    ) -> Result<ExecutionResult> {
+        let started_at = SystemTime::now();
+        let adapter = self.adapter(&dialect)?;
+        let pool = self.pool(connection_string, &dialect)?;
+
+        // Database errors are reported as failed results carrying their
+        // category, so recoverable ones can be retried
+        let mut connection = match pool.get().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                let error = adapter.categorize_error(&e);
+                return Ok(ExecutionResult::new(ExecutionStatus::Failed)
+                    .with_message(format!("Failed to connect: {}", error))
+                    .with_error(error));
+            }
+        };
+
+        let existing = match connection.get_relation_type(&build.relation).await {
+            Ok(existing) => existing,
+            Err(e) => return Ok(error_result(&*connection, &e)),
+        };
+        let updates_in_place = matches!(
+            build.materialization,
+            Materialization::Incremental | Materialization::Snapshot
+        );
+        let columns = if updates_in_place && existing == Some(RelationType::Table) {
+            match connection.get_columns(&build.relation).await {
+                Ok(columns) => columns,
+                Err(e) => return Ok(error_result(&*connection, &e)),
+            }
+        } else {
+            Vec::new()
+        };
+
+        let statements = materialization::build_statements(&adapter.dialect(), build, existing, &columns)?;
+        let canceller = connection.canceller();
+        let (execution, abandoned) = {
+            let mut execution = pin!(connection.execute_statements(&statements));
+            let mut interrupt = pin!(interrupt);
+            let raced = std::future::poll_fn(|cx| {
+                if let Poll::Ready(execution) = execution.as_mut().poll(cx) {
+                    return Poll::Ready(Ok(execution));
+                }
+                interrupt.as_mut().poll(cx).map(Err)
+            })
+            .await;
+
+            match raced {
+                Ok(execution) => (execution, false),
+                Err(interruption) => {
+                    // Cancel on the server and wait for the transaction to roll back,
+                    // or abandon the statements when the platform can't cancel them
+                    let cancelled = match &canceller {
+                        Some(canceller) => canceller.cancel().await.is_ok(),
+                        None => false,
+                    };
+                    if cancelled {
+                        let _ = execution.await;
+                    }
+                    let error = interruption.error();
+                    let result = ExecutionResult::new(ExecutionStatus::Failed)
+                        .with_message(format!("SQL execution failed: {}", error))
+                        .with_error(error);
+                    (Ok(result), !cancelled)
+                }
+            }
+        };
+        let mut result = match execution {
+            Ok(result) => result,
+            Err(e) => error_result(&*connection, &e),
+        };
+        // A connection left mid-transaction can't be reused
+        if abandoned {
+            connection.discard();
+        }
+
+        // Point the error at the line of the model that caused it
+        if let Some(error) = result.error.as_mut()
+            && let (Some(index), Some(position)) = (error.statement_index, error.position)
+            && let Some(statement) = statements.statements.get(index)
+        {
+            error.model_line = model_error_line(statement, position, &build.select_sql);
+        }
+
+        Ok(result.with_started_at(started_at))
+    }
+}
+
+/// Serialize a duration as fractional seconds
+pub fn serialize_seconds<S: Serializer>(duration: &Duration, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
+/// Serialize a point in time as an RFC 3339 timestamp in UTC
+pub fn serialize_timestamp<S: Serializer>(time: &SystemTime, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    let timestamp = time::OffsetDateTime::from(*time)
+        .format(&time::format_description::well_known::Rfc3339)
+        .map_err(serde::ser::Error::custom)?;
+    serializer.serialize_str(&timestamp)
+}
+
+/// Failed result for an error returned by a connection, categorized by its adapter
+fn error_result(connection: &dyn DatabaseConnection, error: &color_eyre::Report) -> ExecutionResult {
+    let details = connection.categorize_error(error);
+    ExecutionResult::new(ExecutionStatus::Failed)
+        .with_message(format!("SQL execution failed: {}", details))
+        .with_error(details)
+}
+
+/// Line of the model SQL that a 1-based character position in a statement
+/// points at, if the position falls within the model SELECT embedded in it
+fn model_error_line(statement: &str, position: usize, select_sql: &str) -> Option<usize> {
+    let offset = statement.char_indices().nth(position.checked_sub(1)?)?.0;
+    let select_sql = materialization::normalize_select(select_sql);
+    let select_start = statement.find(select_sql)?;
+    if offset < select_start || offset > select_start + select_sql.len() {
+        return None;
+    }
+
+    Some(statement[select_start..offset].matches('\n').count() + 1)
+}
+
+impl Default for ExecutionEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+mod connection;
+pub mod pool;
+pub mod scheduler;
+
+/// Create an execution engine with all available adapters registered
+pub fn create_engine_with_available_adapters() -> ExecutionEngine {
+    let mut engine = ExecutionEngine::new();
+    
+    #[cfg(feature = "postgres")]
+    {
+        engine.register_adapter(SqlDialect::Postgres, Box::new(super::postgres::PostgresAdapter));
+    }
+    
+    #[cfg(feature = "databricks")]
+    {
+        // engine.register_adapter(SqlDialect::Databricks, Box::new(databricks::DatabricksAdapter));
+    }
+    
+    #[cfg(feature = "snowflake")]
+    {
+        // engine.register_adapter(SqlDialect::Snowflake, Box::new(snowflake::SnowflakeAdapter));
+    }
+    
+    engine
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_options_default() {
+        let options = RunOptions::default();
+        assert!(options.select.is_empty());
+        assert!(options.exclude.is_empty());
+        assert!(!options.dry_run);
+        assert!(options.fail_fast);
+        assert!(!options.full_refresh);
+        assert!(options.target_database.is_none());
+        assert_eq!(options.threads, 1);
+    }
+
+    #[test]
+    fn test_retry_backoff() {
+        let policy = RetryPolicy {
+            retries: 5,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(3),
+        };
+        assert_eq!(policy.backoff(1), Duration::from_millis(500));
+        assert_eq!(policy.backoff(2), Duration::from_secs(1));
+        assert_eq!(policy.backoff(3), Duration::from_secs(2));
+        assert_eq!(policy.backoff(4), Duration::from_secs(3));
+        assert_eq!(policy.backoff(40), Duration::from_secs(3));
+
+        assert!(ExecutionErrorKind::Connection.is_recoverable());
+        assert!(!ExecutionErrorKind::Syntax.is_recoverable());
+    }
+
+    #[test]
+    fn test_error_kind_from_sqlstate() {
+        assert_eq!(ExecutionErrorKind::from_sqlstate("42601"), ExecutionErrorKind::Syntax);
+        assert_eq!(ExecutionErrorKind::from_sqlstate("42P01"), ExecutionErrorKind::MissingRelation);
+        assert_eq!(ExecutionErrorKind::from_sqlstate("42883"), ExecutionErrorKind::InvalidStatement);
+        assert_eq!(ExecutionErrorKind::from_sqlstate("23502"), ExecutionErrorKind::ConstraintViolation);
+        assert_eq!(ExecutionErrorKind::from_sqlstate("08006"), ExecutionErrorKind::Connection);
+        assert_eq!(ExecutionErrorKind::from_sqlstate("57014"), ExecutionErrorKind::Timeout);
+        assert_eq!(ExecutionErrorKind::from_sqlstate("2BP01"), ExecutionErrorKind::DependentObjects);
+        assert_eq!(ExecutionErrorKind::from_sqlstate("XX000"), ExecutionErrorKind::Unknown);
+        assert!(ExecutionErrorKind::from_sqlstate("40P01").is_recoverable());
+        assert!(ExecutionErrorKind::from_sqlstate("55P03").is_recoverable());
+        assert!(!ExecutionErrorKind::from_sqlstate("57014").is_recoverable());
+    }
+
+    #[test]
+    fn test_model_error_line() {
+        let select_sql = "-- Orders\nSELECT id,\n  amout\nFROM orders;\n";
+        let statement = format!("CREATE TABLE analytics.orders AS {}", materialization::normalize_select(select_sql));
+
+        // Position of "amout" in the statement, 1-based
+        let position = statement.find("amout").unwrap() + 1;
+        assert_eq!(model_error_line(&statement, position, select_sql), Some(3));
+
+        // Errors in the surrounding DDL don't map to a model line
+        assert_eq!(model_error_line(&statement, 1, select_sql), None);
+    }
+
+    #[test]
+    fn test_execution_engine_creation() {
+        let engine = ExecutionEngine::new();
+        assert_eq!(engine.available_dialects().len(), 0);
+    }
+
+    #[test]
+    fn test_create_engine_with_available_adapters() {
+        let engine = create_engine_with_available_adapters();
+        
+        #[cfg(feature = "postgres")]
+        {
+            assert!(engine.supports_dialect(&SqlDialect::Postgres));
+        }
+        
+        #[cfg(not(feature = "postgres"))]
+        {
+            assert!(!engine.supports_dialect(&SqlDialect::Postgres));
+        }
+    }
+}
\ No newline at end of file
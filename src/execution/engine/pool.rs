@@ -0,0 +1,270 @@
+use super::{DatabaseAdapter, DatabaseConnection};
+use color_eyre::Result;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex};
+
+/// Connections to one database, kept open so the models of a run can reuse
+/// them instead of connecting for every model.
+///
+/// The pool doesn't limit how many connections are open at once; the
+/// scheduler already bounds that by the thread count. It keeps at most
+/// `size` idle connections and opens new ones when none are idle.
+pub struct ConnectionPool {
+    adapter: Arc<dyn DatabaseAdapter>,
+    connection_string: String,
+    size: usize,
+    idle: Mutex<Vec<Box<dyn DatabaseConnection>>>,
+}
+
+impl ConnectionPool {
+    pub fn new(adapter: Arc<dyn DatabaseAdapter>, connection_string: &str, size: usize) -> Self {
+        Self {
+            adapter,
+            connection_string: connection_string.to_string(),
+            size: size.max(1),
+            idle: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Hand out an idle connection that passes its health check, or open a
+    /// new one. The connection returns to the pool when it is dropped.
+    pub async fn get(&self) -> Result<PooledConnection<'_>> {
+        loop {
+            let idle = self.idle.lock().unwrap().pop();
+            let Some(connection) = idle else {
+                break;
+            };
+            if connection.is_healthy().await {
+                return Ok(PooledConnection::new(self, connection));
+            }
+            // Broken connections are dropped rather than returned to the pool
+            let _ = connection.close().await;
+        }
+
+        let connection = self.adapter.connect(&self.connection_string).await?;
+        Ok(PooledConnection::new(self, connection))
+    }
+
+    /// Close every idle connection
+    pub async fn close(&self) -> Result<()> {
+        let idle = std::mem::take(&mut *self.idle.lock().unwrap());
+        for connection in idle {
+            connection.close().await?;
+        }
+        Ok(())
+    }
+
+    fn release(&self, connection: Box<dyn DatabaseConnection>) {
+        let mut idle = self.idle.lock().unwrap();
+        if idle.len() < self.size {
+            idle.push(connection);
+        }
+    }
+}
+
+/// A connection borrowed from a [`ConnectionPool`]
+pub struct PooledConnection<'a> {
+    pool: &'a ConnectionPool,
+    connection: Option<Box<dyn DatabaseConnection>>,
+}
+
+impl<'a> PooledConnection<'a> {
+    fn new(pool: &'a ConnectionPool, connection: Box<dyn DatabaseConnection>) -> Self {
+        Self {
+            pool,
+            connection: Some(connection),
+        }
+    }
+
+    /// Drop the connection instead of returning it to the pool, for when its
+    /// state is unknown, e.g. after a statement was abandoned mid-transaction
+    pub fn discard(mut self) {
+        self.connection = None;
+    }
+}
+
+impl Deref for PooledConnection<'_> {
+    type Target = dyn DatabaseConnection;
+
+    fn deref(&self) -> &Self::Target {
+        self.connection.as_deref().expect("pooled connection is present until dropped")
+    }
+}
+
+impl DerefMut for PooledConnection<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.connection.as_deref_mut().expect("pooled connection is present until dropped")
+    }
+}
+
+impl Drop for PooledConnection<'_> {
+    fn drop(&mut self) {
+        if let Some(connection) = self.connection.take() {
+            self.pool.release(connection);
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "postgres")]
+mod tests {
+    use super::*;
+    use crate::execution::materialization::{BuildStatements, ModelBuild, Relation, RelationType};
+    use crate::execution::{
+        ErrorDetails, ExecutionEngine, ExecutionErrorKind, ExecutionResult, ExecutionStatus, SqlDialect,
+    };
+    use crate::parser::{Materialization, ModelConfig};
+    use color_eyre::eyre::eyre;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    fn idle_count(pool: &ConnectionPool) -> usize {
+        pool.idle.lock().unwrap().len()
+    }
+
+    struct FakeConnection {
+        healthy: Arc<AtomicBool>,
+    }
+
+    #[async_trait::async_trait]
+    impl DatabaseConnection for FakeConnection {
+        async fn execute_sql(&self, _sql: &str) -> Result<ExecutionResult> {
+            Ok(ExecutionResult::new(ExecutionStatus::Success))
+        }
+
+        async fn execute_statements(&mut self, _statements: &BuildStatements) -> Result<ExecutionResult> {
+            Ok(ExecutionResult::new(ExecutionStatus::Success))
+        }
+
+        async fn get_relation_type(&self, _relation: &Relation) -> Result<Option<RelationType>> {
+            Ok(None)
+        }
+
+        async fn get_columns(&self, _relation: &Relation) -> Result<Vec<String>> {
+            Ok(Vec::new())
+        }
+
+        fn categorize_error(&self, error: &color_eyre::Report) -> ErrorDetails {
+            ErrorDetails::new(ExecutionErrorKind::Unknown, error.to_string())
+        }
+
+        async fn is_healthy(&self) -> bool {
+            self.healthy.load(Ordering::SeqCst)
+        }
+
+        fn dialect(&self) -> SqlDialect {
+            SqlDialect::Postgres
+        }
+
+        async fn close(&self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[derive(Default)]
+    struct FakeAdapter {
+        connects: AtomicUsize,
+        healthy: Arc<AtomicBool>,
+        /// Kind of error connecting fails with, if it does
+        connect_error: Option<ExecutionErrorKind>,
+    }
+
+    #[async_trait::async_trait]
+    impl DatabaseAdapter for FakeAdapter {
+        async fn connect(&self, _connection_string: &str) -> Result<Box<dyn DatabaseConnection>> {
+            self.connects.fetch_add(1, Ordering::SeqCst);
+            if self.connect_error.is_some() {
+                return Err(eyre!("connection rejected"));
+            }
+            Ok(Box::new(FakeConnection {
+                healthy: self.healthy.clone(),
+            }))
+        }
+
+        fn categorize_error(&self, error: &color_eyre::Report) -> ErrorDetails {
+            let kind = self.connect_error.unwrap_or(ExecutionErrorKind::Unknown);
+            ErrorDetails::new(kind, error.to_string())
+        }
+
+        fn dialect(&self) -> SqlDialect {
+            SqlDialect::Postgres
+        }
+
+        fn validate_connection_string(&self, _connection_string: &str) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pool_reuses_healthy_connections() -> Result<()> {
+        let adapter = Arc::new(FakeAdapter::default());
+        adapter.healthy.store(true, Ordering::SeqCst);
+        let pool = ConnectionPool::new(adapter.clone(), "fake://db", 1);
+
+        drop(pool.get().await?);
+        drop(pool.get().await?);
+        assert_eq!(adapter.connects.load(Ordering::SeqCst), 1);
+
+        // Connections in use at the same time are separate, but only `size` are kept
+        let first = pool.get().await?;
+        let second = pool.get().await?;
+        assert_eq!(adapter.connects.load(Ordering::SeqCst), 2);
+        drop(first);
+        drop(second);
+        assert_eq!(idle_count(&pool), 1);
+
+        // Discarded connections are never handed out again
+        pool.get().await?.discard();
+        assert_eq!(idle_count(&pool), 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pool_replaces_unhealthy_connections() -> Result<()> {
+        let adapter = Arc::new(FakeAdapter::default());
+        adapter.healthy.store(true, Ordering::SeqCst);
+        let pool = ConnectionPool::new(adapter.clone(), "fake://db", 2);
+
+        drop(pool.get().await?);
+        adapter.healthy.store(false, Ordering::SeqCst);
+        drop(pool.get().await?);
+
+        assert_eq!(adapter.connects.load(Ordering::SeqCst), 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_connect_errors_categorized_by_adapter() -> Result<()> {
+        let build = ModelBuild {
+            relation: Relation::new("bronze", "users"),
+            select_sql: "SELECT 1 AS id".to_string(),
+            materialization: Materialization::Table,
+            config: ModelConfig::default(),
+            full_refresh: false,
+            dependent_views: Vec::new(),
+        };
+
+        // A rejected login fails for good, while a lost connection is retried
+        for (kind, recoverable) in [
+            (ExecutionErrorKind::PermissionDenied, false),
+            (ExecutionErrorKind::Connection, true),
+        ] {
+            let mut engine = ExecutionEngine::new();
+            let adapter = FakeAdapter {
+                connect_error: Some(kind),
+                ..FakeAdapter::default()
+            };
+            engine.register_adapter(SqlDialect::Postgres, Box::new(adapter));
+
+            let result = engine
+                .materialize(&build, "fake://db", SqlDialect::Postgres, std::future::pending())
+                .await?;
+            assert_eq!(result.status, ExecutionStatus::Failed);
+            assert_eq!(result.error.as_ref().map(|error| error.kind), Some(kind));
+            assert_eq!(result.is_recoverable_failure(), recoverable);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,394 @@
+use super::{ExecutionResult, ExecutionStatus, Interruption, RetryPolicy};
+use crate::dependency_graph::DependencyGraph;
+use color_eyre::Result;
+use color_eyre::eyre::eyre;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+
+/// Outcome of a single model in a scheduled run
+#[derive(Debug)]
+pub struct ModelOutcome {
+    pub model: String,
+    /// None when the model was never started because the run stopped early
+    pub result: Option<Result<ExecutionResult>>,
+}
+
+/// Get the executed models that must finish before the given model can run,
+/// looking through ephemeral dependencies to the models they select from
+pub fn execution_dependencies(graph: &DependencyGraph, model: &str) -> Vec<String> {
+    let mut dependencies = Vec::new();
+    let mut visited = HashSet::new();
+    let mut stack = graph.get_dependencies(model);
+    while let Some(dependency) = stack.pop() {
+        if !visited.insert(dependency.clone()) {
+            continue;
+        }
+        if graph.is_ephemeral(&dependency) {
+            stack.extend(graph.get_dependencies(&dependency));
+        } else {
+            dependencies.push(dependency);
+        }
+    }
+    dependencies.sort();
+    dependencies
+}
+
+/// Run models on the tokio runtime, launching each one as soon as all of its
+/// upstream models have finished, with at most `threads` running at a time.
+///
+/// `models` must be in execution order. Dependencies on models that are not
+/// part of the run are treated as already built. When a model fails, every
+/// model downstream of it is skipped while unrelated branches keep running.
+/// With `fail_fast` no further models are launched after a failure at all,
+/// but models already running finish.
+/// Outcomes are returned in the order of `models`, whatever order they
+/// completed in.
+pub async fn run_dag<F, Fut>(
+    models: &[String],
+    dependencies: &HashMap<String, Vec<String>>,
+    threads: usize,
+    fail_fast: bool,
+    mut run_model: F,
+) -> Result<Vec<ModelOutcome>>
+where
+    F: FnMut(String) -> Fut,
+    Fut: Future<Output = Result<ExecutionResult>> + Send + 'static,
+{
+    let selected: HashSet<&String> = models.iter().collect();
+    let mut pending: Vec<&String> = models.iter().collect();
+    let mut finished: HashSet<String> = HashSet::new();
+    let mut results: HashMap<String, Result<ExecutionResult>> = HashMap::new();
+    let mut running = JoinSet::new();
+    let mut stopped = false;
+
+    loop {
+        // Launch ready models in execution order until every thread is busy
+        while !stopped && running.len() < threads.max(1) {
+            let ready = pending.iter().position(|model| {
+                dependencies
+                    .get(*model)
+                    .into_iter()
+                    .flatten()
+                    .all(|dependency| {
+                        !selected.contains(dependency) || finished.contains(dependency)
+                    })
+            });
+            let Some(position) = ready else {
+                break;
+            };
+
+            let model = pending.remove(position).clone();
+            let execution = run_model(model.clone());
+            running.spawn(async move { (model, execution.await) });
+        }
+
+        let Some(joined) = running.join_next().await else {
+            break;
+        };
+        let (model, result) = joined.map_err(|e| eyre!("Model execution task failed: {}", e))?;
+
+        let outcome_failed =
+            !matches!(&result, Ok(result) if result.status != ExecutionStatus::Failed);
+        stopped |= fail_fast && outcome_failed;
+        if outcome_failed {
+            // Pending models are in execution order, so a single pass reaches every transitive dependent
+            let mut blocked: HashSet<String> = HashSet::from([model.clone()]);
+            pending.retain(|candidate| {
+                let downstream = dependencies
+                    .get(*candidate)
+                    .into_iter()
+                    .flatten()
+                    .any(|dependency| blocked.contains(dependency));
+                if downstream {
+                    blocked.insert((*candidate).clone());
+                    let skipped = ExecutionResult::new(ExecutionStatus::Skipped)
+                        .with_message(format!("Skipped because upstream model {} failed", model));
+                    results.insert((*candidate).clone(), Ok(skipped));
+                }
+                !downstream
+            });
+        }
+        finished.insert(model.clone());
+        results.insert(model, result);
+    }
+
+    Ok(models
+        .iter()
+        .map(|model| ModelOutcome {
+            model: model.clone(),
+            result: results.remove(model),
+        })
+        .collect())
+}
+
+/// Run a model, retrying it with exponential backoff for as long as it fails
+/// with a recoverable error and the policy allows. The result records how
+/// many attempts were made.
+pub async fn run_with_retry<F, Fut>(policy: &RetryPolicy, mut attempt: F) -> Result<ExecutionResult>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<ExecutionResult>>,
+{
+    let mut attempts = 1;
+    loop {
+        let result = attempt().await?;
+        if !result.is_recoverable_failure() || attempts > policy.retries {
+            return Ok(result.with_attempts(attempts));
+        }
+
+        tokio::time::sleep(policy.backoff(attempts)).await;
+        attempts += 1;
+    }
+}
+
+/// Wait until a model should be interrupted: when its timeout elapses or
+/// the run is cancelled through the `cancelled` channel
+pub async fn wait_for_interruption(
+    timeout: Option<Duration>,
+    mut cancelled: watch::Receiver<bool>,
+) -> Interruption {
+    let timed_out = async {
+        match timeout {
+            Some(timeout) => {
+                tokio::time::sleep(timeout).await;
+                Interruption::Timeout(timeout)
+            }
+            None => std::future::pending().await,
+        }
+    };
+
+    tokio::select! {
+        Ok(_) = cancelled.wait_for(|cancelled| *cancelled) => Interruption::Cancelled,
+        interruption = timed_out => interruption,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execution::{ErrorDetails, ExecutionErrorKind};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    fn models(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    fn dependencies(edges: &[(&str, &[&str])]) -> HashMap<String, Vec<String>> {
+        edges
+            .iter()
+            .map(|(model, upstream)| (model.to_string(), models(upstream)))
+            .collect()
+    }
+
+    #[test]
+    fn test_execution_dependencies() -> Result<()> {
+        // C -> B -> A, with B ephemeral and built on another ephemeral E
+        let mut graph = DependencyGraph::new();
+        graph.add_dependency("B", "A")?;
+        graph.add_dependency("B", "E")?;
+        graph.add_dependency("C", "B")?;
+        graph.mark_ephemeral("B");
+        graph.mark_ephemeral("E");
+
+        // C waits for the executed model its ephemeral dependencies select from
+        assert_eq!(execution_dependencies(&graph, "C"), vec!["A"]);
+        assert!(execution_dependencies(&graph, "A").is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_run_dag_respects_dependencies_and_threads() -> Result<()> {
+        // A, B and C are independent; D selects from A and B
+        let order = models(&["A", "B", "C", "D"]);
+        let dependencies = dependencies(&[("D", &["A", "B"])]);
+
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+        let completed = Arc::new(Mutex::new(Vec::new()));
+
+        let outcomes = run_dag(&order, &dependencies, 2, false, |model| {
+            let running = running.clone();
+            let max_running = max_running.clone();
+            let completed = completed.clone();
+            async move {
+                let now_running = running.fetch_add(1, Ordering::SeqCst) + 1;
+                max_running.fetch_max(now_running, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                completed.lock().unwrap().push(model);
+                Ok(ExecutionResult::new(ExecutionStatus::Success))
+            }
+        })
+        .await?;
+
+        assert_eq!(max_running.load(Ordering::SeqCst), 2);
+        let completed = completed.lock().unwrap();
+        let position = |model: &str| completed.iter().position(|m| m == model).unwrap();
+        assert!(position("D") > position("A"));
+        assert!(position("D") > position("B"));
+
+        // Outcomes follow the execution order, not completion order
+        let outcome_models: Vec<&str> = outcomes.iter().map(|o| o.model.as_str()).collect();
+        assert_eq!(outcome_models, vec!["A", "B", "C", "D"]);
+        assert!(outcomes.iter().all(|o| matches!(o.result, Some(Ok(_)))));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_run_dag_skips_dependents_of_failures() -> Result<()> {
+        // B and C are downstream of A; D is an unrelated branch
+        let order = models(&["A", "D", "B", "C"]);
+        let dependencies = dependencies(&[("B", &["A"]), ("C", &["B"])]);
+
+        let outcomes = run_dag(&order, &dependencies, 2, false, |model| async move {
+            let status = if model == "A" {
+                ExecutionStatus::Failed
+            } else {
+                ExecutionStatus::Success
+            };
+            Ok(ExecutionResult::new(status))
+        })
+        .await?;
+
+        let status = |index: usize| match &outcomes[index].result {
+            Some(Ok(result)) => result.status.clone(),
+            other => panic!("unexpected outcome: {:?}", other),
+        };
+        assert_eq!(status(0), ExecutionStatus::Failed);
+        assert_eq!(status(1), ExecutionStatus::Success);
+        assert_eq!(status(2), ExecutionStatus::Skipped);
+        assert_eq!(status(3), ExecutionStatus::Skipped);
+
+        // Transitive dependents name the model that actually failed
+        let Some(Ok(skipped)) = &outcomes[3].result else {
+            unreachable!()
+        };
+        assert_eq!(
+            skipped.message.as_deref(),
+            Some("Skipped because upstream model A failed")
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_run_dag_fail_fast() -> Result<()> {
+        let order = models(&["A", "B", "C"]);
+        let dependencies = dependencies(&[("B", &["A"])]);
+
+        let outcomes = run_dag(&order, &dependencies, 1, true, |model| async move {
+            if model == "A" {
+                Err(eyre!("relation does not exist"))
+            } else {
+                Ok(ExecutionResult::new(ExecutionStatus::Success))
+            }
+        })
+        .await?;
+
+        assert!(matches!(outcomes[0].result, Some(Err(_))));
+        // Dependents are skipped and nothing else is launched once a model has failed
+        assert!(
+            matches!(&outcomes[1].result, Some(Ok(result)) if result.status == ExecutionStatus::Skipped)
+        );
+        assert!(outcomes[2].result.is_none());
+
+        Ok(())
+    }
+
+    fn failed(kind: ExecutionErrorKind) -> ExecutionResult {
+        ExecutionResult::new(ExecutionStatus::Failed).with_error(ErrorDetails::new(kind, "error"))
+    }
+
+    #[tokio::test]
+    async fn test_run_with_retry() -> Result<()> {
+        let policy = RetryPolicy {
+            retries: 3,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(5),
+        };
+
+        // Connection errors are retried until the model succeeds
+        let calls = AtomicUsize::new(0);
+        let result = run_with_retry(&policy, || {
+            let call = calls.fetch_add(1, Ordering::SeqCst);
+            async move {
+                Ok(if call < 2 {
+                    failed(ExecutionErrorKind::Connection)
+                } else {
+                    ExecutionResult::new(ExecutionStatus::Success)
+                })
+            }
+        })
+        .await?;
+        assert_eq!(result.status, ExecutionStatus::Success);
+        assert_eq!(result.attempts, 3);
+
+        // Errors in the model itself fail on the first attempt
+        let result =
+            run_with_retry(&policy, || async { Ok(failed(ExecutionErrorKind::Syntax)) }).await?;
+        assert_eq!(result.attempts, 1);
+
+        // Retries stop once the policy is exhausted
+        let result = run_with_retry(&policy, || async {
+            Ok(failed(ExecutionErrorKind::Serialization))
+        })
+        .await?;
+        assert_eq!(result.status, ExecutionStatus::Failed);
+        assert_eq!(result.attempts, 4);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_timed_out_model_runs_once() -> Result<()> {
+        let policy = RetryPolicy {
+            retries: 3,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(5),
+        };
+        let (_cancel, cancelled) = watch::channel(false);
+
+        // The model runs past its timeout and is interrupted
+        let calls = AtomicUsize::new(0);
+        let result = run_with_retry(&policy, || {
+            calls.fetch_add(1, Ordering::SeqCst);
+            let cancelled = cancelled.clone();
+            async move {
+                let interruption =
+                    wait_for_interruption(Some(Duration::from_millis(5)), cancelled).await;
+                Ok(ExecutionResult::new(ExecutionStatus::Failed).with_error(interruption.error()))
+            }
+        })
+        .await?;
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(result.attempts, 1);
+        assert_eq!(result.error.map(|error| error.kind), Some(ExecutionErrorKind::Timeout));
+
+        // So is a statement cancelled on the server, which reports query_canceled
+        let result = run_with_retry(&policy, || async {
+            Ok(failed(ExecutionErrorKind::from_sqlstate("57014")))
+        })
+        .await?;
+        assert_eq!(result.attempts, 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_interruption() {
+        let (cancel, cancelled) = watch::channel(false);
+
+        let interruption = wait_for_interruption(Some(Duration::from_millis(5)), cancelled.clone()).await;
+        assert_eq!(interruption, Interruption::Timeout(Duration::from_millis(5)));
+
+        cancel.send(true).unwrap();
+        let interruption = wait_for_interruption(Some(Duration::from_secs(60)), cancelled).await;
+        assert_eq!(interruption, Interruption::Cancelled);
+    }
+}
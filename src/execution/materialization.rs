@@ -0,0 +1,715 @@
+use super::SqlDialect;
+use crate::parser::{IncrementalStrategy, Materialization, ModelConfig, SnapshotStrategy};
+use color_eyre::Result;
+use color_eyre::eyre::eyre;
+use std::ops::Range;
+
+/// Suffix appended to a relation name while it is being built
+const TEMP_RELATION_SUFFIX: &str = "__cadac_tmp";
+
+/// Column holding the time a snapshot row version became valid
+pub const VALID_FROM_COLUMN: &str = "valid_from";
+/// Column holding the time a snapshot row version was superseded
+pub const VALID_TO_COLUMN: &str = "valid_to";
+/// Column flagging the current version of a snapshot row
+pub const IS_CURRENT_COLUMN: &str = "is_current";
+
+/// Keywords that must be quoted when used as an identifier
+const RESERVED_KEYWORDS: &[&str] = &[
+    "all", "and", "as", "by", "case", "check", "column", "create", "default", "distinct", "else",
+    "end", "from", "grant", "group", "having", "in", "into", "join", "limit", "not", "null", "on",
+    "or", "order", "select", "table", "then", "to", "union", "user", "using", "when", "where",
+    "with",
+];
+
+/// A physical relation (table or view) in the target database
+#[derive(Debug, Clone, PartialEq)]
+pub struct Relation {
+    /// Database the relation is qualified with, on platforms that reference across databases
+    pub database: Option<String>,
+    pub schema: String,
+    pub name: String,
+}
+
+impl Relation {
+    pub fn new(schema: &str, name: &str) -> Self {
+        Self {
+            database: None,
+            schema: schema.to_string(),
+            name: name.to_string(),
+        }
+    }
+
+    /// Qualify the relation with a database
+    pub fn with_database(mut self, database: Option<String>) -> Self {
+        self.database = database;
+        self
+    }
+
+    /// The relation used to build the model before it is swapped into place
+    pub fn temp_relation(&self) -> Self {
+        Self {
+            name: format!("{}{}", self.name, TEMP_RELATION_SUFFIX),
+            ..self.clone()
+        }
+    }
+
+    /// The schema, qualified with the database if any and quoted where needed
+    pub fn qualified_schema(&self) -> String {
+        match &self.database {
+            Some(database) => format!("{}.{}", quote_identifier(database), quote_identifier(&self.schema)),
+            None => quote_identifier(&self.schema),
+        }
+    }
+
+    /// The relation name, quoted where needed
+    pub fn quoted_name(&self) -> String {
+        quote_identifier(&self.name)
+    }
+}
+
+impl std::fmt::Display for Relation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.qualified_schema(), self.quoted_name())
+    }
+}
+
+/// Quote an identifier unless it is a plain lowercase name that every
+/// platform reads back unchanged
+pub fn quote_identifier(identifier: &str) -> String {
+    let is_plain = identifier
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_lowercase() || c == '_')
+        && identifier
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        && !RESERVED_KEYWORDS.contains(&identifier);
+
+    if is_plain {
+        identifier.to_string()
+    } else {
+        format!("\"{}\"", identifier.replace('"', "\"\""))
+    }
+}
+
+/// Kind of relation that currently exists in the target database
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RelationType {
+    Table,
+    View,
+}
+
+impl RelationType {
+    fn keyword(&self) -> &'static str {
+        match self {
+            RelationType::Table => "TABLE",
+            RelationType::View => "VIEW",
+        }
+    }
+}
+
+/// Everything needed to materialize a single model
+#[derive(Debug, Clone)]
+pub struct ModelBuild {
+    /// Relation the model is materialized into
+    pub relation: Relation,
+    /// The model's SELECT statement
+    pub select_sql: String,
+    /// Resolved materialization of the model
+    pub materialization: Materialization,
+    /// Configuration declared by the model
+    pub config: ModelConfig,
+    /// Rebuild incremental models from scratch
+    pub full_refresh: bool,
+    /// Views of other models selecting from this one, directly or through
+    /// other views. They are dropped before the relation is replaced and
+    /// rebuilt when their own models run.
+    pub dependent_views: Vec<Relation>,
+}
+
+/// The statements that materialize a model, executed as a single transaction
+#[derive(Debug, Clone, PartialEq)]
+pub struct BuildStatements {
+    pub statements: Vec<String>,
+    /// Statements whose affected rows are reported for the model, e.g. the
+    /// INSERT applying staged rows but not the CREATE TABLE staging them
+    pub counted: Range<usize>,
+}
+
+impl BuildStatements {
+    /// Statements that all count towards the model's affected rows
+    pub fn all_counted(statements: Vec<String>) -> Self {
+        let counted = 0..statements.len();
+        Self {
+            statements,
+            counted,
+        }
+    }
+
+    /// Statements that stage rows, apply them with the given statements, and
+    /// clean up, where only the applying statements count
+    fn applied(staging: Vec<String>, apply: Vec<String>, cleanup: String) -> Self {
+        let counted = staging.len()..staging.len() + apply.len();
+        let mut statements = staging;
+        statements.extend(apply);
+        statements.push(cleanup);
+        Self {
+            statements,
+            counted,
+        }
+    }
+}
+
+/// Dialect-specific SQL for applying new rows to an existing incremental model.
+///
+/// New rows are first written to a staging relation, then applied to the target
+/// using the model's strategy. Dialects expose their implementation through
+/// `SqlDialect::incremental_sql`.
+pub trait IncrementalSql: Send + Sync {
+    /// Insert all staged rows into the target
+    fn append_statements(&self, target: &Relation, staging: &Relation, columns: &[String]) -> Vec<String>;
+
+    /// Update target rows matching the unique key and insert the remaining staged rows
+    fn merge_statements(
+        &self,
+        target: &Relation,
+        staging: &Relation,
+        unique_key: &[String],
+        columns: &[String],
+    ) -> Vec<String>;
+
+    /// Delete target rows matching the unique key, then insert all staged rows
+    fn delete_insert_statements(
+        &self,
+        target: &Relation,
+        staging: &Relation,
+        unique_key: &[String],
+        columns: &[String],
+    ) -> Vec<String>;
+
+    /// Build all statements of an incremental run, given the target's columns.
+    /// Only the rows the strategy applies to the target are counted.
+    fn incremental_statements(&self, build: &ModelBuild, columns: &[String]) -> BuildStatements {
+        let target = &build.relation;
+        let staging = target.temp_relation();
+        let select_sql = incremental_select(
+            &build.select_sql,
+            target,
+            build.config.incremental_column.as_deref(),
+        );
+        let unique_key = &build.config.unique_key;
+
+        let apply = match build.config.resolved_incremental_strategy() {
+            IncrementalStrategy::Append => self.append_statements(target, &staging, columns),
+            IncrementalStrategy::Merge => {
+                self.merge_statements(target, &staging, unique_key, columns)
+            }
+            IncrementalStrategy::DeleteInsert => {
+                self.delete_insert_statements(target, &staging, unique_key, columns)
+            }
+        };
+        BuildStatements::applied(
+            staging_statements(&staging, &select_sql),
+            apply,
+            format!("DROP TABLE IF EXISTS {}", staging),
+        )
+    }
+}
+
+/// Dialect-specific SQL for maintaining snapshot (SCD type 2) history tables.
+///
+/// Every version of a row is kept, with `valid_from`/`valid_to` bounding the
+/// period it was current and `is_current` flagging the latest version. Dialects
+/// expose their implementation through `SqlDialect::snapshot_sql`.
+pub trait SnapshotSql: Send + Sync {
+    /// Create the history table from the first snapshot of the model
+    fn create_snapshot_statements(
+        &self,
+        target: &Relation,
+        select_sql: &str,
+        strategy: &SnapshotStrategy,
+    ) -> Vec<String>;
+
+    /// Close the current version of changed rows and insert new versions for
+    /// changed and new rows from the staging relation
+    fn update_snapshot_statements(
+        &self,
+        target: &Relation,
+        staging: &Relation,
+        unique_key: &[String],
+        strategy: &SnapshotStrategy,
+        columns: &[String],
+    ) -> Vec<String>;
+
+    /// Build all statements of a snapshot run, given the columns of the existing
+    /// history table (empty when it doesn't exist yet). Only the row versions
+    /// closed and inserted in the history table are counted.
+    fn snapshot_statements(
+        &self,
+        build: &ModelBuild,
+        strategy: &SnapshotStrategy,
+        columns: &[String],
+    ) -> BuildStatements {
+        let target = &build.relation;
+        if columns.is_empty() {
+            return BuildStatements::all_counted(self.create_snapshot_statements(
+                target,
+                &build.select_sql,
+                strategy,
+            ));
+        }
+
+        // Only the model's own columns are compared and copied
+        let columns: Vec<String> = columns
+            .iter()
+            .filter(|column| {
+                ![VALID_FROM_COLUMN, VALID_TO_COLUMN, IS_CURRENT_COLUMN].contains(&column.as_str())
+            })
+            .cloned()
+            .collect();
+
+        let staging = target.temp_relation();
+        BuildStatements::applied(
+            staging_statements(&staging, normalize_select(&build.select_sql)),
+            self.update_snapshot_statements(
+                target,
+                &staging,
+                &build.config.unique_key,
+                strategy,
+                &columns,
+            ),
+            format!("DROP TABLE IF EXISTS {}", staging),
+        )
+    }
+}
+
+/// Write the rows selected by a run into a staging relation
+pub fn staging_statements(staging: &Relation, select_sql: &str) -> Vec<String> {
+    vec![
+        format!("DROP TABLE IF EXISTS {}", staging),
+        format!("CREATE TABLE {} AS\n{}\n", staging, select_sql),
+    ]
+}
+
+/// Restrict a model's SELECT to rows newer than what the target already holds
+pub fn incremental_select(select_sql: &str, target: &Relation, incremental_column: Option<&str>) -> String {
+    let select_sql = normalize_select(select_sql);
+
+    match incremental_column {
+        Some(column) => format!(
+            "SELECT * FROM (\n{}\n) AS cadac_source\nWHERE (SELECT MAX({column}) FROM {target}) IS NULL\n   OR cadac_source.{column} > (SELECT MAX({column}) FROM {target})",
+            select_sql,
+            column = quote_identifier(column),
+            target = target,
+        ),
+        None => select_sql.to_string(),
+    }
+}
+
+/// Strip trailing semicolons and whitespace so the model SELECT can be embedded in DDL
+pub fn normalize_select(sql: &str) -> &str {
+    sql.trim_end_matches(|c: char| c == ';' || c.is_whitespace())
+}
+
+/// Build the statements that materialize a model, given the kind of relation
+/// that currently exists at the target (if any) and its columns.
+///
+/// Table and view models are always rebuilt. Incremental and snapshot models
+/// are updated in place once their table exists, using the dialect's SQL.
+pub fn build_statements(
+    dialect: &SqlDialect,
+    build: &ModelBuild,
+    existing: Option<RelationType>,
+    columns: &[String],
+) -> Result<BuildStatements> {
+    let relation = &build.relation;
+
+    match build.materialization {
+        Materialization::Table => Ok(BuildStatements::all_counted(table_statements(
+            dialect,
+            relation,
+            &build.select_sql,
+            existing,
+            &build.dependent_views,
+        ))),
+        Materialization::View => Ok(BuildStatements::all_counted(view_statements(
+            dialect,
+            relation,
+            &build.select_sql,
+            existing,
+            &build.dependent_views,
+        ))),
+        Materialization::Incremental => {
+            let incremental = dialect.incremental_sql().ok_or_else(|| {
+                eyre!("Incremental models are not supported for dialect: {:?}", dialect)
+            })?;
+            // Without a watermark every run would append the full SELECT again
+            if build.config.resolved_incremental_strategy() == IncrementalStrategy::Append
+                && build.config.incremental_column.is_none()
+            {
+                return Err(eyre!(
+                    "Incremental model {} uses the append strategy and requires an incremental_column",
+                    relation
+                ));
+            }
+
+            // Incremental models are built like tables on their first run
+            if existing == Some(RelationType::Table) && !build.full_refresh {
+                Ok(incremental.incremental_statements(build, columns))
+            } else {
+                Ok(BuildStatements::all_counted(table_statements(
+                    dialect,
+                    relation,
+                    &build.select_sql,
+                    existing,
+                    &build.dependent_views,
+                )))
+            }
+        }
+        Materialization::Snapshot => {
+            let snapshot = dialect.snapshot_sql().ok_or_else(|| {
+                eyre!("Snapshot models are not supported for dialect: {:?}", dialect)
+            })?;
+            let strategy = build.config.snapshot_strategy().ok_or_else(|| {
+                eyre!("Snapshot model {} requires exactly one of updated_at or check_cols", relation)
+            })?;
+
+            // History is never rebuilt, not even on a full refresh
+            Ok(snapshot.snapshot_statements(build, &strategy, columns))
+        }
+        Materialization::Ephemeral => Err(eyre!(
+            "Ephemeral model {} is inlined into its dependents and is never materialized",
+            relation
+        )),
+    }
+}
+
+/// Build the statements that materialize a SELECT as a table.
+///
+/// The statements are meant to be executed atomically. The model is built into
+/// a temporary relation first so the existing table stays readable until the
+/// new one is swapped in.
+pub fn table_statements(
+    dialect: &SqlDialect,
+    relation: &Relation,
+    select_sql: &str,
+    existing: Option<RelationType>,
+    dependent_views: &[Relation],
+) -> Vec<String> {
+    let select_sql = normalize_select(select_sql);
+
+    match dialect {
+        SqlDialect::Postgres => {
+            let temp = relation.temp_relation();
+            let replaced = existing.unwrap_or(RelationType::Table);
+            let mut statements = vec![
+                format!("CREATE SCHEMA IF NOT EXISTS {}", relation.qualified_schema()),
+                format!("DROP TABLE IF EXISTS {}", temp),
+                format!("CREATE TABLE {} AS\n{}\n", temp, select_sql),
+            ];
+            statements.extend(drop_dependent_views(dependent_views));
+            // Without CASCADE, so other objects depending on the relation fail
+            // the build instead of being dropped along with it
+            statements.push(format!("DROP {} IF EXISTS {}", replaced.keyword(), relation));
+            statements.push(format!("ALTER TABLE {} RENAME TO {}", temp, relation.quoted_name()));
+            statements
+        }
+        // Both platforms replace tables atomically
+        SqlDialect::Databricks | SqlDialect::Snowflake => {
+            let mut statements = vec![format!("CREATE SCHEMA IF NOT EXISTS {}", relation.qualified_schema())];
+            if existing == Some(RelationType::View) {
+                statements.push(format!("DROP VIEW IF EXISTS {}", relation));
+            }
+            statements.push(format!("CREATE OR REPLACE TABLE {} AS\n{}\n", relation, select_sql));
+            statements
+        }
+    }
+}
+
+/// Build the statements that materialize a SELECT as a view, dropping a table
+/// of the same name when the model switched from a table materialization
+pub fn view_statements(
+    dialect: &SqlDialect,
+    relation: &Relation,
+    select_sql: &str,
+    existing: Option<RelationType>,
+    dependent_views: &[Relation],
+) -> Vec<String> {
+    let select_sql = normalize_select(select_sql);
+
+    let mut statements = vec![format!("CREATE SCHEMA IF NOT EXISTS {}", relation.qualified_schema())];
+    match dialect {
+        // CREATE OR REPLACE VIEW can't drop, rename or retype columns on Postgres,
+        // so the view is dropped and created again within the transaction
+        SqlDialect::Postgres => {
+            if let Some(existing) = existing {
+                statements.extend(drop_dependent_views(dependent_views));
+                statements.push(format!("DROP {} IF EXISTS {}", existing.keyword(), relation));
+            }
+            statements.push(format!("CREATE VIEW {} AS\n{}\n", relation, select_sql));
+        }
+        SqlDialect::Databricks | SqlDialect::Snowflake => {
+            if existing == Some(RelationType::Table) {
+                statements.push(format!("DROP TABLE IF EXISTS {}", relation));
+            }
+            statements.push(format!("CREATE OR REPLACE VIEW {} AS\n{}\n", relation, select_sql));
+        }
+    }
+    statements
+}
+
+/// Drop the views of other models that would keep a relation from being
+/// dropped on Postgres, which binds views to the relations they select from
+fn drop_dependent_views(dependent_views: &[Relation]) -> Option<String> {
+    if dependent_views.is_empty() {
+        return None;
+    }
+    let views: Vec<String> = dependent_views.iter().map(Relation::to_string).collect();
+    Some(format!("DROP VIEW IF EXISTS {}", views.join(", ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_temp_relation() {
+        let relation = Relation::new("bronze", "users");
+
+        assert_eq!(relation.to_string(), "bronze.users");
+        assert_eq!(relation.temp_relation().to_string(), "bronze.users__cadac_tmp");
+    }
+
+    #[test]
+    fn test_relation_quoting() {
+        assert_eq!(Relation::new("dev_alice_bronze", "users").to_string(), "dev_alice_bronze.users");
+        assert_eq!(Relation::new("gold", "Order").to_string(), "gold.\"Order\"");
+        assert_eq!(Relation::new("gold", "order").to_string(), "gold.\"order\"");
+        assert_eq!(Relation::new("my-schema", "a\"b").to_string(), "\"my-schema\".\"a\"\"b\"");
+
+        let relation = Relation::new("gold", "orders").with_database(Some("Analytics".to_string()));
+        assert_eq!(relation.to_string(), "\"Analytics\".gold.orders");
+        assert_eq!(relation.temp_relation().to_string(), "\"Analytics\".gold.orders__cadac_tmp");
+    }
+
+    #[test]
+    fn test_incremental_select() {
+        let target = Relation::new("gold", "orders");
+
+        assert_eq!(incremental_select("SELECT 1;", &target, None), "SELECT 1");
+
+        let select = incremental_select("SELECT * FROM raw.orders", &target, Some("updated_at"));
+        assert_eq!(
+            select,
+            "SELECT * FROM (\nSELECT * FROM raw.orders\n) AS cadac_source\n\
+             WHERE (SELECT MAX(updated_at) FROM gold.orders) IS NULL\n   \
+             OR cadac_source.updated_at > (SELECT MAX(updated_at) FROM gold.orders)"
+        );
+    }
+
+    #[test]
+    fn test_normalize_select() {
+        assert_eq!(normalize_select("SELECT 1;\n"), "SELECT 1");
+        assert_eq!(normalize_select("SELECT 1 ; ;"), "SELECT 1");
+        assert_eq!(normalize_select("SELECT 1"), "SELECT 1");
+    }
+
+    #[test]
+    fn test_postgres_table_statements() {
+        let relation = Relation::new("bronze", "users");
+        let statements = table_statements(
+            &SqlDialect::Postgres,
+            &relation,
+            "SELECT id FROM sources.users;",
+            None,
+            &[],
+        );
+
+        assert_eq!(
+            statements,
+            vec![
+                "CREATE SCHEMA IF NOT EXISTS bronze",
+                "DROP TABLE IF EXISTS bronze.users__cadac_tmp",
+                "CREATE TABLE bronze.users__cadac_tmp AS\nSELECT id FROM sources.users\n",
+                "DROP TABLE IF EXISTS bronze.users",
+                "ALTER TABLE bronze.users__cadac_tmp RENAME TO users",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_postgres_table_with_dependent_views() {
+        let relation = Relation::new("bronze", "users");
+        let dependent_views = [
+            Relation::new("silver", "customers"),
+            Relation::new("gold", "active_customers"),
+        ];
+        let statements = table_statements(
+            &SqlDialect::Postgres,
+            &relation,
+            "SELECT 1",
+            Some(RelationType::Table),
+            &dependent_views,
+        );
+
+        // The views of dependent models are dropped before the swap, nothing cascades
+        assert_eq!(
+            statements[3..],
+            [
+                "DROP VIEW IF EXISTS silver.customers, gold.active_customers",
+                "DROP TABLE IF EXISTS bronze.users",
+                "ALTER TABLE bronze.users__cadac_tmp RENAME TO users",
+            ]
+        );
+        assert!(!statements.iter().any(|statement| statement.contains("CASCADE")));
+
+        // Other platforms replace tables without invalidating their views
+        let statements = table_statements(
+            &SqlDialect::Snowflake,
+            &relation,
+            "SELECT 1",
+            Some(RelationType::Table),
+            &dependent_views,
+        );
+        assert!(!statements.iter().any(|statement| statement.starts_with("DROP VIEW")));
+    }
+
+    #[test]
+    fn test_snowflake_table_statements() {
+        let relation = Relation::new("gold", "orders");
+        let statements = table_statements(&SqlDialect::Snowflake, &relation, "SELECT 1", None, &[]);
+
+        assert_eq!(statements.len(), 2);
+        assert_eq!(statements[1], "CREATE OR REPLACE TABLE gold.orders AS\nSELECT 1\n");
+    }
+
+    #[test]
+    fn test_postgres_table_replacing_view() {
+        let relation = Relation::new("bronze", "users");
+        let statements = table_statements(
+            &SqlDialect::Postgres,
+            &relation,
+            "SELECT 1",
+            Some(RelationType::View),
+            &[],
+        );
+
+        assert!(statements.contains(&"DROP VIEW IF EXISTS bronze.users".to_string()));
+        assert!(!statements.iter().any(|s| s.starts_with("DROP TABLE IF EXISTS bronze.users ")));
+    }
+
+    #[test]
+    fn test_view_statements() {
+        let relation = Relation::new("staging", "users");
+        let statements = view_statements(
+            &SqlDialect::Postgres,
+            &relation,
+            "SELECT id FROM raw.users;",
+            None,
+            &[],
+        );
+
+        assert_eq!(
+            statements,
+            vec![
+                "CREATE SCHEMA IF NOT EXISTS staging",
+                "CREATE VIEW staging.users AS\nSELECT id FROM raw.users\n",
+            ]
+        );
+
+        // An existing view is dropped rather than replaced, so its columns can change
+        let dependent_views = [Relation::new("gold", "users")];
+        let statements = view_statements(
+            &SqlDialect::Postgres,
+            &relation,
+            "SELECT id, email FROM raw.users",
+            Some(RelationType::View),
+            &dependent_views,
+        );
+        assert_eq!(
+            statements,
+            vec![
+                "CREATE SCHEMA IF NOT EXISTS staging",
+                "DROP VIEW IF EXISTS gold.users",
+                "DROP VIEW IF EXISTS staging.users",
+                "CREATE VIEW staging.users AS\nSELECT id, email FROM raw.users\n",
+            ]
+        );
+
+        let statements = view_statements(
+            &SqlDialect::Snowflake,
+            &relation,
+            "SELECT 1",
+            Some(RelationType::View),
+            &dependent_views,
+        );
+        assert_eq!(
+            statements[1..],
+            ["CREATE OR REPLACE VIEW staging.users AS\nSELECT 1\n"]
+        );
+    }
+
+    #[test]
+    fn test_view_statements_replacing_table() {
+        let relation = Relation::new("staging", "users");
+
+        let dependent_views = [Relation::new("gold", "users")];
+        let statements = view_statements(
+            &SqlDialect::Postgres,
+            &relation,
+            "SELECT 1",
+            Some(RelationType::Table),
+            &dependent_views,
+        );
+        assert_eq!(statements[1], "DROP VIEW IF EXISTS gold.users");
+        assert_eq!(statements[2], "DROP TABLE IF EXISTS staging.users");
+
+        let statements = view_statements(
+            &SqlDialect::Databricks,
+            &relation,
+            "SELECT 1",
+            Some(RelationType::Table),
+            &dependent_views,
+        );
+        assert_eq!(statements[1], "DROP TABLE IF EXISTS staging.users");
+    }
+
+    #[test]
+    fn test_counted_statements() -> Result<()> {
+        let mut build = ModelBuild {
+            relation: Relation::new("gold", "orders"),
+            select_sql: "SELECT order_id, amount FROM raw.orders".to_string(),
+            materialization: Materialization::Incremental,
+            config: ModelConfig::default(),
+            full_refresh: false,
+            dependent_views: Vec::new(),
+        };
+        build.config.incremental_strategy = Some(IncrementalStrategy::Merge);
+        build.config.unique_key = vec!["order_id".to_string()];
+        let columns = vec!["order_id".to_string(), "amount".to_string()];
+
+        // Staging the new rows doesn't count, only the UPDATE and INSERT applying them
+        let statements =
+            build_statements(&SqlDialect::Postgres, &build, Some(RelationType::Table), &columns)?;
+        let counted = &statements.statements[statements.counted.clone()];
+        assert_eq!(counted.len(), 2);
+        assert!(counted[0].starts_with("UPDATE gold.orders AS t"));
+        assert!(counted[1].starts_with("INSERT INTO gold.orders "));
+
+        // The first run creates the table, which counts as a whole
+        let statements = build_statements(&SqlDialect::Postgres, &build, None, &[])?;
+        assert_eq!(statements.counted, 0..statements.statements.len());
+
+        build.materialization = Materialization::Snapshot;
+        build.config.updated_at = Some("updated_at".to_string());
+        let statements =
+            build_statements(&SqlDialect::Postgres, &build, Some(RelationType::Table), &columns)?;
+        let counted = &statements.statements[statements.counted.clone()];
+        assert!(counted[0].starts_with("UPDATE gold.orders AS t SET valid_to"));
+        assert!(counted[1].starts_with("INSERT INTO gold.orders "));
+        assert_eq!(counted.len(), 2);
+
+        Ok(())
+    }
+}
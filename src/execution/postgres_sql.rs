@@ -0,0 +1,276 @@
+use super::materialization::{
+    quote_identifier, IncrementalSql, Relation, SnapshotSql, IS_CURRENT_COLUMN, VALID_FROM_COLUMN,
+    VALID_TO_COLUMN,
+};
+use crate::parser::SnapshotStrategy;
+
+/// SQL generation for PostgreSQL incremental and snapshot models. It needs no
+/// connection, so models can be compiled without the postgres feature.
+pub struct PostgresSql;
+
+/// Quote column names, which come from the model's SELECT and may need it
+fn quote_columns(columns: &[String]) -> Vec<String> {
+    columns.iter().map(|column| quote_identifier(column)).collect()
+}
+
+/// Prefix already quoted columns with a table alias
+fn aliased_columns(alias: &str, columns: &[String]) -> String {
+    columns
+        .iter()
+        .map(|column| format!("{}.{}", alias, column))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Join condition matching target and staging rows on the unique key
+fn unique_key_condition(unique_key: &[String]) -> String {
+    quote_columns(unique_key)
+        .iter()
+        .map(|key| format!("t.{key} = s.{key}", key = key))
+        .collect::<Vec<_>>()
+        .join(" AND ")
+}
+
+/// Incremental strategies for PostgreSQL, using UPDATE/INSERT rather than MERGE
+/// so they work on versions before 15
+impl IncrementalSql for PostgresSql {
+    fn append_statements(&self, target: &Relation, staging: &Relation, columns: &[String]) -> Vec<String> {
+        let column_list = quote_columns(columns).join(", ");
+        vec![format!(
+            "INSERT INTO {} ({}) SELECT {} FROM {}",
+            target, column_list, column_list, staging
+        )]
+    }
+
+    fn merge_statements(
+        &self,
+        target: &Relation,
+        staging: &Relation,
+        unique_key: &[String],
+        columns: &[String],
+    ) -> Vec<String> {
+        let quoted = quote_columns(columns);
+        let condition = unique_key_condition(unique_key);
+        let assignments: Vec<String> = columns
+            .iter()
+            .filter(|column| !unique_key.contains(column))
+            .map(|column| format!("{column} = s.{column}", column = quote_identifier(column)))
+            .collect();
+
+        let mut statements = Vec::new();
+        // A key-only table has nothing to update
+        if !assignments.is_empty() {
+            statements.push(format!(
+                "UPDATE {} AS t SET {} FROM {} AS s WHERE {}",
+                target, assignments.join(", "), staging, condition
+            ));
+        }
+        statements.push(format!(
+            "INSERT INTO {} ({}) SELECT {} FROM {} AS s WHERE NOT EXISTS (SELECT 1 FROM {} AS t WHERE {})",
+            target,
+            quoted.join(", "),
+            aliased_columns("s", &quoted),
+            staging,
+            target,
+            condition
+        ));
+        statements
+    }
+
+    fn delete_insert_statements(
+        &self,
+        target: &Relation,
+        staging: &Relation,
+        unique_key: &[String],
+        columns: &[String],
+    ) -> Vec<String> {
+        let column_list = quote_columns(columns).join(", ");
+        vec![
+            format!(
+                "DELETE FROM {} AS t USING {} AS s WHERE {}",
+                target, staging, unique_key_condition(unique_key)
+            ),
+            format!(
+                "INSERT INTO {} ({}) SELECT {} FROM {}",
+                target, column_list, column_list, staging
+            ),
+        ]
+    }
+}
+
+/// Time at which a row version became valid under the given snapshot strategy
+fn snapshot_valid_from(strategy: &SnapshotStrategy, source_alias: &str) -> String {
+    match strategy {
+        SnapshotStrategy::Timestamp(column) => {
+            format!("CAST({}.{} AS TIMESTAMP)", source_alias, quote_identifier(column))
+        }
+        // CURRENT_TIMESTAMP is fixed for the whole transaction
+        SnapshotStrategy::Check(_) => "CAST(CURRENT_TIMESTAMP AS TIMESTAMP)".to_string(),
+    }
+}
+
+/// Snapshots for PostgreSQL, closing changed rows with an UPDATE and inserting
+/// every key that has no current version left
+impl SnapshotSql for PostgresSql {
+    fn create_snapshot_statements(
+        &self,
+        target: &Relation,
+        select_sql: &str,
+        strategy: &SnapshotStrategy,
+    ) -> Vec<String> {
+        vec![
+            format!("CREATE SCHEMA IF NOT EXISTS {}", target.qualified_schema()),
+            format!(
+                "CREATE TABLE {} AS\nSELECT cadac_source.*, {} AS {}, CAST(NULL AS TIMESTAMP) AS {}, TRUE AS {}\nFROM (\n{}\n) AS cadac_source",
+                target,
+                snapshot_valid_from(strategy, "cadac_source"),
+                VALID_FROM_COLUMN,
+                VALID_TO_COLUMN,
+                IS_CURRENT_COLUMN,
+                super::materialization::normalize_select(select_sql)
+            ),
+        ]
+    }
+
+    fn update_snapshot_statements(
+        &self,
+        target: &Relation,
+        staging: &Relation,
+        unique_key: &[String],
+        strategy: &SnapshotStrategy,
+        columns: &[String],
+    ) -> Vec<String> {
+        let quoted = quote_columns(columns);
+        let condition = unique_key_condition(unique_key);
+        let valid_from = snapshot_valid_from(strategy, "s");
+        let changed = match strategy {
+            SnapshotStrategy::Timestamp(_) => format!("{} > t.{}", valid_from, VALID_FROM_COLUMN),
+            SnapshotStrategy::Check(check_cols) => format!(
+                "({})",
+                check_cols
+                    .iter()
+                    .map(|column| {
+                        format!("t.{column} IS DISTINCT FROM s.{column}", column = quote_identifier(column))
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" OR ")
+            ),
+        };
+
+        vec![
+            format!(
+                "UPDATE {} AS t SET {} = {}, {} = FALSE FROM {} AS s WHERE {} AND t.{} AND {}",
+                target,
+                VALID_TO_COLUMN,
+                valid_from,
+                IS_CURRENT_COLUMN,
+                staging,
+                condition,
+                IS_CURRENT_COLUMN,
+                changed
+            ),
+            format!(
+                "INSERT INTO {} ({}, {}, {}, {}) SELECT {}, {}, NULL, TRUE FROM {} AS s WHERE NOT EXISTS (SELECT 1 FROM {} AS t WHERE {} AND t.{})",
+                target,
+                quoted.join(", "),
+                VALID_FROM_COLUMN,
+                VALID_TO_COLUMN,
+                IS_CURRENT_COLUMN,
+                aliased_columns("s", &quoted),
+                valid_from,
+                staging,
+                target,
+                condition,
+                IS_CURRENT_COLUMN
+            ),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_postgres_merge_statements() {
+        let sql = PostgresSql;
+        let target = Relation::new("gold", "orders");
+        let staging = target.temp_relation();
+        let columns = vec!["order_id".to_string(), "amount".to_string()];
+
+        let statements = sql.merge_statements(&target, &staging, &["order_id".to_string()], &columns);
+        assert_eq!(
+            statements,
+            vec![
+                "UPDATE gold.orders AS t SET amount = s.amount FROM gold.orders__cadac_tmp AS s WHERE t.order_id = s.order_id",
+                "INSERT INTO gold.orders (order_id, amount) SELECT s.order_id, s.amount FROM gold.orders__cadac_tmp AS s WHERE NOT EXISTS (SELECT 1 FROM gold.orders AS t WHERE t.order_id = s.order_id)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_postgres_check_snapshot_statements() {
+        let sql = PostgresSql;
+        let target = Relation::new("snapshots", "users");
+        let staging = target.temp_relation();
+        let strategy = SnapshotStrategy::Check(vec!["email".to_string()]);
+        let columns = vec!["user_id".to_string(), "email".to_string()];
+
+        let statements = sql.update_snapshot_statements(&target, &staging, &["user_id".to_string()], &strategy, &columns);
+        assert_eq!(
+            statements,
+            vec![
+                "UPDATE snapshots.users AS t SET valid_to = CAST(CURRENT_TIMESTAMP AS TIMESTAMP), is_current = FALSE FROM snapshots.users__cadac_tmp AS s WHERE t.user_id = s.user_id AND t.is_current AND (t.email IS DISTINCT FROM s.email)",
+                "INSERT INTO snapshots.users (user_id, email, valid_from, valid_to, is_current) SELECT s.user_id, s.email, CAST(CURRENT_TIMESTAMP AS TIMESTAMP), NULL, TRUE FROM snapshots.users__cadac_tmp AS s WHERE NOT EXISTS (SELECT 1 FROM snapshots.users AS t WHERE t.user_id = s.user_id AND t.is_current)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_postgres_delete_insert_statements() {
+        let sql = PostgresSql;
+        let target = Relation::new("gold", "orders");
+        let staging = target.temp_relation();
+        let unique_key = vec!["order_id".to_string(), "line".to_string()];
+        let columns = vec!["order_id".to_string(), "line".to_string()];
+
+        let statements = sql.delete_insert_statements(&target, &staging, &unique_key, &columns);
+        assert_eq!(
+            statements[0],
+            "DELETE FROM gold.orders AS t USING gold.orders__cadac_tmp AS s WHERE t.order_id = s.order_id AND t.line = s.line"
+        );
+        assert_eq!(
+            statements[1],
+            "INSERT INTO gold.orders (order_id, line) SELECT order_id, line FROM gold.orders__cadac_tmp"
+        );
+    }
+
+    #[test]
+    fn test_postgres_statements_quote_columns() {
+        let sql = PostgresSql;
+        let target = Relation::new("gold", "orders");
+        let staging = target.temp_relation();
+        let unique_key = vec!["Order ID".to_string()];
+        let columns = vec!["Order ID".to_string(), "order".to_string(), "amount".to_string()];
+
+        let statements = sql.merge_statements(&target, &staging, &unique_key, &columns);
+        assert_eq!(
+            statements,
+            vec![
+                "UPDATE gold.orders AS t SET \"order\" = s.\"order\", amount = s.amount FROM gold.orders__cadac_tmp AS s WHERE t.\"Order ID\" = s.\"Order ID\"",
+                "INSERT INTO gold.orders (\"Order ID\", \"order\", amount) SELECT s.\"Order ID\", s.\"order\", s.amount FROM gold.orders__cadac_tmp AS s WHERE NOT EXISTS (SELECT 1 FROM gold.orders AS t WHERE t.\"Order ID\" = s.\"Order ID\")",
+            ]
+        );
+
+        let statements = sql.append_statements(&target, &staging, &columns);
+        assert_eq!(
+            statements[0],
+            "INSERT INTO gold.orders (\"Order ID\", \"order\", amount) SELECT \"Order ID\", \"order\", amount FROM gold.orders__cadac_tmp"
+        );
+
+        let strategy = SnapshotStrategy::Timestamp("UpdatedAt".to_string());
+        let statements = sql.update_snapshot_statements(&target, &staging, &unique_key, &strategy, &columns);
+        assert!(statements[0].contains("CAST(s.\"UpdatedAt\" AS TIMESTAMP) > t.valid_from"));
+        assert!(statements[1].starts_with("INSERT INTO gold.orders (\"Order ID\", \"order\", amount, valid_from"));
+    }
+}
@@ -1,7 +1,10 @@
 // Snowflake adapter implementation
 // This will be implemented when snowflake feature is added
 
-use super::{DatabaseAdapter, DatabaseConnection, ExecutionResult, ExecutionStatus, SqlDialect};
+use super::materialization::{BuildStatements, Relation, RelationType};
+use super::{
+    DatabaseAdapter, DatabaseConnection, ExecutionErrorKind, ErrorDetails, ExecutionResult, ExecutionStatus, SqlDialect,
+};
 use color_eyre::Result;
 
 /// Snowflake connection implementation (placeholder)
@@ -14,6 +17,26 @@ impl DatabaseConnection for SnowflakeConnection {
         unimplemented!("Snowflake adapter not yet implemented")
     }
 
+    async fn execute_statements(&mut self, _statements: &BuildStatements) -> Result<ExecutionResult> {
+        // TODO: Implement Snowflake statement execution
+        unimplemented!("Snowflake adapter not yet implemented")
+    }
+
+    async fn get_relation_type(&self, _relation: &Relation) -> Result<Option<RelationType>> {
+        // TODO: Implement Snowflake relation lookup
+        unimplemented!("Snowflake adapter not yet implemented")
+    }
+
+    async fn get_columns(&self, _relation: &Relation) -> Result<Vec<String>> {
+        // TODO: Implement Snowflake column lookup
+        unimplemented!("Snowflake adapter not yet implemented")
+    }
+
+    fn categorize_error(&self, error: &color_eyre::Report) -> ErrorDetails {
+        // TODO: Categorize Snowflake errors
+        ErrorDetails::new(ExecutionErrorKind::Unknown, error.to_string())
+    }
+
     fn dialect(&self) -> SqlDialect {
         SqlDialect::Snowflake
     }
@@ -33,6 +56,11 @@ impl DatabaseAdapter for SnowflakeAdapter {
         unimplemented!("Snowflake adapter not yet implemented")
     }
 
+    fn categorize_error(&self, error: &color_eyre::Report) -> ErrorDetails {
+        // TODO: Categorize Snowflake connection errors
+        ErrorDetails::new(ExecutionErrorKind::Unknown, error.to_string())
+    }
+
     fn dialect(&self) -> SqlDialect {
         SqlDialect::Snowflake
     }
@@ -1,7 +1,10 @@
 #[cfg(test)]
 #[cfg(feature = "postgres")]
 mod tests {
-    use crate::execution::{create_engine_with_available_adapters, SqlDialect, ExecutionStatus, DatabaseAdapter, DatabaseConnection};
+    use crate::execution::{create_engine_with_available_adapters, SqlDialect, ExecutionStatus, ExecutionErrorKind, DatabaseAdapter, DatabaseConnection};
+    use crate::execution::materialization::{ModelBuild, Relation};
+    use crate::execution::scheduler::wait_for_interruption;
+    use crate::parser::{IncrementalStrategy, Materialization, ModelConfig};
     use testcontainers_modules::{postgres, testcontainers::runners::AsyncRunner};
     use tokio;
     use std::time::Duration;
@@ -21,6 +24,18 @@ mod tests {
         Ok(container)
     }
 
+    /// Helper function to describe a model build without any model config
+    fn model_build(relation: &Relation, select_sql: &str, materialization: Materialization) -> ModelBuild {
+        ModelBuild {
+            relation: relation.clone(),
+            select_sql: select_sql.to_string(),
+            materialization,
+            config: ModelConfig::default(),
+            full_refresh: false,
+            dependent_views: Vec::new(),
+        }
+    }
+
     /// Helper function to create connection string from container
     async fn get_connection_string(container: &testcontainers_modules::testcontainers::ContainerAsync<postgres::Postgres>) -> Result<String, String> {
         let host = container.get_host().await.map_err(|e| format!("Failed to get host: {}", e))?;
@@ -294,12 +309,12 @@ mod tests {
         
         // Test various error scenarios
         let error_scenarios = vec![
-            ("Syntax error", "SELCT * FROM nonexistent"),
-            ("Missing table", "SELECT * FROM nonexistent_table"),
-            ("Invalid column", "SELECT nonexistent_column FROM information_schema.tables LIMIT 1"),
+            ("Syntax error", "SELCT * FROM nonexistent", ExecutionErrorKind::Syntax),
+            ("Missing table", "SELECT * FROM nonexistent_table", ExecutionErrorKind::MissingRelation),
+            ("Invalid column", "SELECT nonexistent_column FROM information_schema.tables LIMIT 1", ExecutionErrorKind::MissingColumn),
         ];
         
-        for (scenario_name, sql) in error_scenarios {
+        for (scenario_name, sql, expected_kind) in error_scenarios {
             let result = engine.execute_sql(sql, &connection_string, SqlDialect::Postgres).await;
             
             // Should return Ok but with Failed status
@@ -307,6 +322,10 @@ mod tests {
             let execution_result = result.unwrap();
             assert_eq!(execution_result.status, ExecutionStatus::Failed, "Scenario '{}' should have Failed status", scenario_name);
             assert!(execution_result.message.is_some(), "Scenario '{}' should have error message", scenario_name);
+            let error = execution_result.error.expect("failed results carry the database error");
+            assert_eq!(error.kind, expected_kind, "Scenario '{}' has the wrong error kind", scenario_name);
+            assert!(error.code.is_some(), "Scenario '{}' should report its SQLSTATE", scenario_name);
+            assert!(error.position.is_some(), "Scenario '{}' should report the error position", scenario_name);
         }
         
         // Test that the connection can still work after errors
@@ -597,4 +616,415 @@ mod tests {
             assert_eq!(result.unwrap().status, ExecutionStatus::Success, "Query '{}' did not succeed", test_name);
         }
     }
+
+    #[tokio::test]
+    async fn test_table_materialization() {
+        let postgres_container = match start_postgres_container().await {
+            Ok(container) => container,
+            Err(e) => {
+                println!("Skipping integration test due to container startup failure: {}", e);
+                return;
+            }
+        };
+
+        let connection_string = match get_connection_string(&postgres_container).await {
+            Ok(conn_str) => conn_str,
+            Err(e) => {
+                println!("Skipping test due to connection string error: {}", e);
+                return;
+            }
+        };
+
+        let engine = create_engine_with_available_adapters();
+
+        let setup = "CREATE TABLE raw_users AS SELECT generate_series(1, 3) AS user_id";
+        let result = engine.execute_sql(setup, &connection_string, SqlDialect::Postgres).await;
+        assert_eq!(result.unwrap().status, ExecutionStatus::Success);
+
+        // First run creates the schema and the table
+        let users = Relation::new("bronze", "users");
+        let result = engine.materialize(&model_build(&users, "SELECT user_id FROM raw_users;", Materialization::Table), &connection_string, SqlDialect::Postgres, std::future::pending()).await;
+        assert!(result.is_ok());
+        let execution_result = result.unwrap();
+        assert_eq!(execution_result.status, ExecutionStatus::Success);
+        assert!(execution_result.query_hash.is_some());
+
+        // Downstream models read what the upstream model produced
+        let orders = Relation::new("gold", "orders");
+        let result = engine.materialize(&model_build(&orders, "SELECT user_id, user_id * 10 AS amount FROM bronze.users", Materialization::Table), &connection_string, SqlDialect::Postgres, std::future::pending()).await;
+        assert_eq!(result.unwrap().status, ExecutionStatus::Success);
+
+        let result = engine.execute_sql("SELECT * FROM gold.orders", &connection_string, SqlDialect::Postgres).await;
+        assert_eq!(result.unwrap().rows_affected, 3);
+
+        // Rebuilding swaps in the new contents and leaves no temp relation behind
+        let result = engine.materialize(&model_build(&users, "SELECT user_id FROM raw_users WHERE user_id > 1", Materialization::Table), &connection_string, SqlDialect::Postgres, std::future::pending()).await;
+        assert_eq!(result.unwrap().status, ExecutionStatus::Success);
+
+        let result = engine.execute_sql("SELECT * FROM bronze.users", &connection_string, SqlDialect::Postgres).await;
+        assert_eq!(result.unwrap().rows_affected, 2);
+
+        let result = engine.execute_sql("SELECT * FROM bronze.users__cadac_tmp", &connection_string, SqlDialect::Postgres).await;
+        assert_eq!(result.unwrap().status, ExecutionStatus::Failed);
+
+        // A failing build leaves the existing table untouched and points at the offending model line
+        let result = engine.materialize(&model_build(&users, "-- Users\nSELECT user_id,\n  missing_column\nFROM raw_users", Materialization::Table), &connection_string, SqlDialect::Postgres, std::future::pending()).await;
+        let execution_result = result.unwrap();
+        assert_eq!(execution_result.status, ExecutionStatus::Failed);
+        let error = execution_result.error.unwrap();
+        assert_eq!(error.kind, ExecutionErrorKind::MissingColumn);
+        assert_eq!(error.code.as_deref(), Some("42703"));
+        assert_eq!(error.model_line, Some(3));
+
+        let result = engine.execute_sql("SELECT * FROM bronze.users", &connection_string, SqlDialect::Postgres).await;
+        assert_eq!(result.unwrap().rows_affected, 2);
+    }
+
+    #[tokio::test]
+    async fn test_materialization_timeout_cancels_query() {
+        let postgres_container = match start_postgres_container().await {
+            Ok(container) => container,
+            Err(e) => {
+                println!("Skipping integration test due to container startup failure: {}", e);
+                return;
+            }
+        };
+
+        let connection_string = match get_connection_string(&postgres_container).await {
+            Ok(conn_str) => conn_str,
+            Err(e) => {
+                println!("Skipping test due to connection string error: {}", e);
+                return;
+            }
+        };
+
+        let engine = create_engine_with_available_adapters();
+        let slow = Relation::new("bronze", "slow");
+        let (_cancel, cancelled) = tokio::sync::watch::channel(false);
+        let interrupt = wait_for_interruption(Some(Duration::from_secs(1)), cancelled);
+
+        let started = std::time::Instant::now();
+        let result = engine.materialize(&model_build(&slow, "SELECT pg_sleep(30) AS slept", Materialization::Table), &connection_string, SqlDialect::Postgres, interrupt).await;
+        let execution_result = result.unwrap();
+
+        // The query is cancelled rather than waited out, and its table never created
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert_eq!(execution_result.status, ExecutionStatus::Failed);
+        assert_eq!(execution_result.error.unwrap().kind, ExecutionErrorKind::Timeout);
+
+        let result = engine.execute_sql("SELECT * FROM bronze.slow", &connection_string, SqlDialect::Postgres).await;
+        assert_eq!(result.unwrap().status, ExecutionStatus::Failed);
+    }
+
+    #[tokio::test]
+    async fn test_view_materialization_switching_kinds() {
+        let postgres_container = match start_postgres_container().await {
+            Ok(container) => container,
+            Err(e) => {
+                println!("Skipping integration test due to container startup failure: {}", e);
+                return;
+            }
+        };
+
+        let connection_string = match get_connection_string(&postgres_container).await {
+            Ok(conn_str) => conn_str,
+            Err(e) => {
+                println!("Skipping test due to connection string error: {}", e);
+                return;
+            }
+        };
+
+        let engine = create_engine_with_available_adapters();
+        let relation = Relation::new("staging", "users");
+        let select = "SELECT generate_series(1, 3) AS user_id";
+
+        // Start out as a table
+        let result = engine.materialize(&model_build(&relation, select, Materialization::Table), &connection_string, SqlDialect::Postgres, std::future::pending()).await;
+        assert_eq!(result.unwrap().status, ExecutionStatus::Success);
+
+        // Switching to a view drops the previous table
+        let result = engine.materialize(&model_build(&relation, select, Materialization::View), &connection_string, SqlDialect::Postgres, std::future::pending()).await;
+        assert_eq!(result.unwrap().status, ExecutionStatus::Success);
+
+        let view_check = "SELECT 1 FROM information_schema.views WHERE table_schema = 'staging' AND table_name = 'users'";
+        let result = engine.execute_sql(view_check, &connection_string, SqlDialect::Postgres).await;
+        assert_eq!(result.unwrap().rows_affected, 1);
+
+        // Re-running the view replaces it in place
+        let result = engine.materialize(&model_build(&relation, select, Materialization::View), &connection_string, SqlDialect::Postgres, std::future::pending()).await;
+        assert_eq!(result.unwrap().status, ExecutionStatus::Success);
+
+        // And switching back to a table drops the view
+        let result = engine.materialize(&model_build(&relation, select, Materialization::Table), &connection_string, SqlDialect::Postgres, std::future::pending()).await;
+        assert_eq!(result.unwrap().status, ExecutionStatus::Success);
+
+        let result = engine.execute_sql(view_check, &connection_string, SqlDialect::Postgres).await;
+        assert_eq!(result.unwrap().rows_affected, 0);
+
+        let result = engine.execute_sql("SELECT * FROM staging.users", &connection_string, SqlDialect::Postgres).await;
+        assert_eq!(result.unwrap().rows_affected, 3);
+    }
+
+    #[tokio::test]
+    async fn test_incremental_materialization_strategies() {
+        let postgres_container = match start_postgres_container().await {
+            Ok(container) => container,
+            Err(e) => {
+                println!("Skipping integration test due to container startup failure: {}", e);
+                return;
+            }
+        };
+
+        let connection_string = match get_connection_string(&postgres_container).await {
+            Ok(conn_str) => conn_str,
+            Err(e) => {
+                println!("Skipping test due to connection string error: {}", e);
+                return;
+            }
+        };
+
+        let engine = create_engine_with_available_adapters();
+
+        let setup_statements = vec![
+            "CREATE TABLE raw_orders (order_id INTEGER, amount INTEGER, updated_at INTEGER)",
+            "INSERT INTO raw_orders VALUES (1, 10, 1), (2, 20, 1)",
+        ];
+        for sql in setup_statements {
+            let result = engine.execute_sql(sql, &connection_string, SqlDialect::Postgres).await;
+            assert_eq!(result.unwrap().status, ExecutionStatus::Success);
+        }
+
+        let select = "SELECT order_id, amount, updated_at FROM raw_orders";
+        let builds = vec![
+            (Relation::new("gold", "orders_append"), IncrementalStrategy::Append, 4),
+            (Relation::new("gold", "orders_merge"), IncrementalStrategy::Merge, 3),
+            (Relation::new("gold", "orders_delete_insert"), IncrementalStrategy::DeleteInsert, 3),
+        ];
+
+        // First run creates every table from the full SELECT
+        for (relation, strategy, _) in &builds {
+            let mut build = model_build(relation, select, Materialization::Incremental);
+            build.config.incremental_strategy = Some(*strategy);
+            build.config.unique_key = vec!["order_id".to_string()];
+            build.config.incremental_column = Some("updated_at".to_string());
+            let result = engine.materialize(&build, &connection_string, SqlDialect::Postgres, std::future::pending()).await;
+            assert_eq!(result.unwrap().status, ExecutionStatus::Success);
+        }
+
+        // Order 2 changes and order 3 arrives
+        let result = engine.execute_sql("INSERT INTO raw_orders VALUES (2, 25, 2), (3, 30, 2)", &connection_string, SqlDialect::Postgres).await;
+        assert_eq!(result.unwrap().status, ExecutionStatus::Success);
+        let result = engine.execute_sql("DELETE FROM raw_orders WHERE order_id = 2 AND updated_at = 1", &connection_string, SqlDialect::Postgres).await;
+        assert_eq!(result.unwrap().status, ExecutionStatus::Success);
+
+        // Later runs only process rows newer than the current watermark, and report the
+        // rows applied to the target rather than the ones written to the staging table
+        for ((relation, strategy, expected_rows), reported_rows) in builds.iter().zip([2, 2, 3]) {
+            let mut build = model_build(relation, select, Materialization::Incremental);
+            build.config.incremental_strategy = Some(*strategy);
+            build.config.unique_key = vec!["order_id".to_string()];
+            build.config.incremental_column = Some("updated_at".to_string());
+            let result = engine.materialize(&build, &connection_string, SqlDialect::Postgres, std::future::pending()).await;
+            let execution_result = result.unwrap();
+            assert_eq!(execution_result.status, ExecutionStatus::Success, "{} failed", strategy);
+            assert_eq!(execution_result.rows_affected, reported_rows, "{} reported unexpected rows", strategy);
+
+            let count = format!("SELECT * FROM {}", relation);
+            let result = engine.execute_sql(&count, &connection_string, SqlDialect::Postgres).await;
+            assert_eq!(result.unwrap().rows_affected, *expected_rows, "{} produced unexpected rows", strategy);
+        }
+
+        let updated = "SELECT * FROM gold.orders_merge WHERE order_id = 2 AND amount = 25";
+        let result = engine.execute_sql(updated, &connection_string, SqlDialect::Postgres).await;
+        assert_eq!(result.unwrap().rows_affected, 1);
+
+        // A full refresh rebuilds the table from the current SELECT
+        let mut build = model_build(&builds[0].0, select, Materialization::Incremental);
+        build.config.incremental_column = Some("updated_at".to_string());
+        build.full_refresh = true;
+        let result = engine.materialize(&build, &connection_string, SqlDialect::Postgres, std::future::pending()).await;
+        assert_eq!(result.unwrap().status, ExecutionStatus::Success);
+
+        let result = engine.execute_sql("SELECT * FROM gold.orders_append", &connection_string, SqlDialect::Postgres).await;
+        assert_eq!(result.unwrap().rows_affected, 3);
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_materialization() {
+        let postgres_container = match start_postgres_container().await {
+            Ok(container) => container,
+            Err(e) => {
+                println!("Skipping integration test due to container startup failure: {}", e);
+                return;
+            }
+        };
+
+        let connection_string = match get_connection_string(&postgres_container).await {
+            Ok(conn_str) => conn_str,
+            Err(e) => {
+                println!("Skipping test due to connection string error: {}", e);
+                return;
+            }
+        };
+
+        let engine = create_engine_with_available_adapters();
+
+        let setup_statements = vec![
+            "CREATE TABLE raw_users (user_id INTEGER, plan TEXT, updated_at TIMESTAMP)",
+            "INSERT INTO raw_users VALUES (1, 'free', '2024-01-01'), (2, 'free', '2024-01-01')",
+        ];
+        for sql in setup_statements {
+            let result = engine.execute_sql(sql, &connection_string, SqlDialect::Postgres).await;
+            assert_eq!(result.unwrap().status, ExecutionStatus::Success);
+        }
+
+        let relation = Relation::new("snapshots", "users");
+        let mut build = model_build(&relation, "SELECT user_id, plan, updated_at FROM raw_users", Materialization::Snapshot);
+        build.config.unique_key = vec!["user_id".to_string()];
+        build.config.updated_at = Some("updated_at".to_string());
+
+        let result = engine.materialize(&build, &connection_string, SqlDialect::Postgres, std::future::pending()).await;
+        assert_eq!(result.unwrap().status, ExecutionStatus::Success);
+
+        // User 1 upgrades; user 2 is untouched
+        let result = engine.execute_sql("UPDATE raw_users SET plan = 'pro', updated_at = '2024-02-01' WHERE user_id = 1", &connection_string, SqlDialect::Postgres).await;
+        assert_eq!(result.unwrap().status, ExecutionStatus::Success);
+
+        // Running twice must not record the same change twice. The first run closes
+        // one version and inserts another, the second finds nothing to change.
+        for reported_rows in [2, 0] {
+            let result = engine.materialize(&build, &connection_string, SqlDialect::Postgres, std::future::pending()).await;
+            let execution_result = result.unwrap();
+            assert_eq!(execution_result.status, ExecutionStatus::Success);
+            assert_eq!(execution_result.rows_affected, reported_rows);
+        }
+
+        let result = engine.execute_sql("SELECT * FROM snapshots.users", &connection_string, SqlDialect::Postgres).await;
+        assert_eq!(result.unwrap().rows_affected, 3);
+
+        let closed = "SELECT * FROM snapshots.users WHERE user_id = 1 AND plan = 'free' AND NOT is_current AND valid_to = '2024-02-01'";
+        let result = engine.execute_sql(closed, &connection_string, SqlDialect::Postgres).await;
+        assert_eq!(result.unwrap().rows_affected, 1);
+
+        let current = "SELECT * FROM snapshots.users WHERE is_current AND valid_to IS NULL";
+        let result = engine.execute_sql(current, &connection_string, SqlDialect::Postgres).await;
+        assert_eq!(result.unwrap().rows_affected, 2);
+    }
+
+    /// PEM files for a TLS test: a CA and the server and client certificates it signed
+    struct TestCertificates {
+        ca: String,
+        server_cert: String,
+        server_key: String,
+        client_cert: String,
+        client_key: String,
+    }
+
+    /// Helper function to generate a self-signed CA, a server certificate for
+    /// localhost and a client certificate for the postgres user
+    fn generate_certificates() -> TestCertificates {
+        use rcgen::{BasicConstraints, CertificateParams, DnType, IsCa, KeyPair};
+
+        let ca_key = KeyPair::generate().unwrap();
+        let mut ca_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        ca_params.distinguished_name.push(DnType::CommonName, "cadac test CA");
+        let ca = ca_params.self_signed(&ca_key).unwrap();
+
+        let server_key = KeyPair::generate().unwrap();
+        let server_cert = CertificateParams::new(vec!["localhost".to_string(), "127.0.0.1".to_string()])
+            .unwrap()
+            .signed_by(&server_key, &ca, &ca_key)
+            .unwrap();
+
+        // Certificate authentication maps the common name to the database user
+        let client_key = KeyPair::generate().unwrap();
+        let mut client_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+        client_params.distinguished_name.push(DnType::CommonName, "postgres");
+        let client_cert = client_params.signed_by(&client_key, &ca, &ca_key).unwrap();
+
+        TestCertificates {
+            ca: ca.pem(),
+            server_cert: server_cert.pem(),
+            server_key: server_key.serialize_pem(),
+            client_cert: client_cert.pem(),
+            client_key: client_key.serialize_pem(),
+        }
+    }
+
+    /// Helper function to start a PostgreSQL container that only accepts TLS
+    /// connections authenticated by a client certificate
+    async fn start_tls_postgres_container(certificates: &TestCertificates) -> Result<testcontainers_modules::testcontainers::ContainerAsync<postgres::Postgres>, String> {
+        use testcontainers_modules::testcontainers::ImageExt;
+
+        let hba = "local all all trust\nhostssl all all all cert\n";
+        // PostgreSQL refuses a key readable by others, so the files are installed with
+        // the right owner and mode before the image's entrypoint starts the server
+        let script = "install -o postgres -m 600 /tmp/certs/* /var/lib/postgresql/ && exec docker-entrypoint.sh postgres \
+            -c ssl=on \
+            -c ssl_cert_file=/var/lib/postgresql/server.crt \
+            -c ssl_key_file=/var/lib/postgresql/server.key \
+            -c ssl_ca_file=/var/lib/postgresql/ca.crt \
+            -c hba_file=/var/lib/postgresql/pg_hba.conf";
+
+        postgres::Postgres::default()
+            .with_copy_to("/tmp/certs/ca.crt", certificates.ca.clone().into_bytes())
+            .with_copy_to("/tmp/certs/server.crt", certificates.server_cert.clone().into_bytes())
+            .with_copy_to("/tmp/certs/server.key", certificates.server_key.clone().into_bytes())
+            .with_copy_to("/tmp/certs/pg_hba.conf", hba.as_bytes().to_vec())
+            .with_cmd(["sh", "-c", script])
+            .start()
+            .await
+            .map_err(|e| format!("Failed to start postgres container with TLS. Error: {}", e))
+    }
+
+    #[tokio::test]
+    async fn test_postgres_tls_connections() {
+        let certificates = generate_certificates();
+        let postgres_container = match start_tls_postgres_container(&certificates).await {
+            Ok(container) => container,
+            Err(e) => {
+                println!("Skipping integration test due to container startup failure: {}", e);
+                return;
+            }
+        };
+
+        let cert_dir = tempfile::tempdir().unwrap();
+        let ca_path = cert_dir.path().join("ca.crt");
+        let client_cert_path = cert_dir.path().join("client.crt");
+        let client_key_path = cert_dir.path().join("client.key");
+        std::fs::write(&ca_path, &certificates.ca).unwrap();
+        std::fs::write(&client_cert_path, &certificates.client_cert).unwrap();
+        std::fs::write(&client_key_path, &certificates.client_key).unwrap();
+
+        let host = postgres_container.get_host().await.unwrap();
+        let port = postgres_container.get_host_port_ipv4(5432).await.unwrap();
+        let connection_string = |parameters: &str| format!("postgresql://postgres@{}:{}/postgres?{}", host, port, parameters);
+        let client_identity = format!("sslcert={}&sslkey={}", client_cert_path.display(), client_key_path.display());
+
+        let engine = create_engine_with_available_adapters();
+        let uses_tls = "SELECT 1 FROM pg_stat_ssl WHERE pid = pg_backend_pid() AND ssl";
+
+        // Fully verified against the CA bundle, authenticated by client certificate
+        let verified = connection_string(&format!("sslmode=verify-full&sslrootcert={}&{}", ca_path.display(), client_identity));
+        let result = engine.execute_sql(uses_tls, &verified, SqlDialect::Postgres).await;
+        assert_eq!(result.unwrap().rows_affected, 1);
+
+        // require encrypts without verifying the self-signed chain
+        let required = connection_string(&format!("sslmode=require&{}", client_identity));
+        let result = engine.execute_sql(uses_tls, &required, SqlDialect::Postgres).await;
+        assert_eq!(result.unwrap().rows_affected, 1);
+
+        // The server only accepts TLS connections
+        let disabled = connection_string(&format!("sslmode=disable&{}", client_identity));
+        assert!(engine.execute_sql("SELECT 1", &disabled, SqlDialect::Postgres).await.is_err());
+
+        // The self-signed CA isn't trusted without the bundle
+        let untrusted = connection_string(&format!("sslmode=verify-ca&{}", client_identity));
+        assert!(engine.execute_sql("SELECT 1", &untrusted, SqlDialect::Postgres).await.is_err());
+
+        // Certificate authentication fails without a client certificate
+        let anonymous = connection_string(&format!("sslmode=verify-full&sslrootcert={}", ca_path.display()));
+        assert!(engine.execute_sql("SELECT 1", &anonymous, SqlDialect::Postgres).await.is_err());
+    }
 }
@@ -0,0 +1,396 @@
+use color_eyre::Result;
+use color_eyre::eyre::Context;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::discovery::ModelCatalog;
+
+/// Kind of node in an exported lineage graph
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeType {
+    /// A model in the project
+    Model,
+    /// An external table a model selects from
+    Source,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GraphNode {
+    /// Qualified name, e.g. `bronze.users`
+    pub id: String,
+    #[serde(rename = "type")]
+    pub node_type: NodeType,
+    /// Schema folder of a model, or the schema a source is qualified with
+    pub schema: Option<String>,
+    /// Materialization of a model
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub materialized: Option<String>,
+    /// SQL file of a model
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+}
+
+/// Data flowing from one node into another, i.e. `to` selects from `from`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct GraphEdge {
+    pub from: String,
+    pub to: String,
+}
+
+/// Lineage of the selected models and the external sources they select from,
+/// ready to be rendered as DOT, Mermaid or JSON
+#[derive(Debug, Serialize)]
+pub struct LineageGraph {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+impl LineageGraph {
+    /// Build the graph of the selected models. Edges between a selected and an
+    /// unselected model are left out.
+    pub fn new(catalog: &ModelCatalog, selected: &BTreeSet<String>) -> Result<Self> {
+        let mut nodes = BTreeMap::new();
+        let mut edges = BTreeSet::new();
+
+        for model_name in selected {
+            let identity = catalog.model_identities.get(model_name);
+            nodes.insert(
+                model_name.clone(),
+                GraphNode {
+                    id: model_name.clone(),
+                    node_type: NodeType::Model,
+                    schema: identity.map(|identity| identity.schema_name.clone()),
+                    materialized: Some(catalog.get_materialization(model_name).to_string()),
+                    path: identity.map(|identity| identity.file_path.display().to_string()),
+                },
+            );
+
+            for dependency in catalog.get_dependencies(model_name) {
+                if selected.contains(&dependency) {
+                    edges.insert(GraphEdge {
+                        from: dependency,
+                        to: model_name.clone(),
+                    });
+                }
+            }
+
+            let Some(model) = catalog.models.get(model_name) else {
+                continue;
+            };
+            // Sources in subqueries only show up in the references
+            let source_ids: BTreeSet<&String> = model
+                .sources
+                .iter()
+                .map(|source| &source.id)
+                .chain(
+                    model
+                        .references
+                        .iter()
+                        .map(|reference| &reference.source_id),
+                )
+                .collect();
+            for source_id in source_ids {
+                if model.is_cte(source_id)
+                    || catalog.resolve_reference(model_name, source_id)?.is_some()
+                {
+                    continue;
+                }
+                nodes.entry(source_id.clone()).or_insert_with(|| GraphNode {
+                    id: source_id.clone(),
+                    node_type: NodeType::Source,
+                    schema: source_schema(source_id),
+                    materialized: None,
+                    path: None,
+                });
+                edges.insert(GraphEdge {
+                    from: source_id.clone(),
+                    to: model_name.clone(),
+                });
+            }
+        }
+
+        Ok(Self {
+            nodes: nodes.into_values().collect(),
+            edges: edges.into_iter().collect(),
+        })
+    }
+
+    /// Nodes grouped by schema, with the nodes without a schema under None
+    fn nodes_by_schema(&self) -> BTreeMap<Option<&str>, Vec<&GraphNode>> {
+        let mut groups: BTreeMap<Option<&str>, Vec<&GraphNode>> = BTreeMap::new();
+        for node in &self.nodes {
+            groups.entry(node.schema.as_deref()).or_default().push(node);
+        }
+        groups
+    }
+
+    /// Render as a Graphviz digraph with a cluster per schema. Models are
+    /// boxes and sources dashed cylinders.
+    pub fn to_dot(&self) -> String {
+        let mut lines = vec!["digraph cadac {".to_string(), "    rankdir=LR;".to_string()];
+        for (schema, nodes) in self.nodes_by_schema() {
+            let indent = if schema.is_some() { "        " } else { "    " };
+            if let Some(schema) = schema {
+                lines.push(format!(
+                    "    subgraph {} {{",
+                    dot_id(&format!("cluster_{}", schema))
+                ));
+                lines.push(format!("        label={};", dot_id(schema)));
+            }
+            for node in nodes {
+                let shape = match node.node_type {
+                    NodeType::Model => "shape=box",
+                    NodeType::Source => "shape=cylinder, style=dashed",
+                };
+                lines.push(format!("{}{} [{}];", indent, dot_id(&node.id), shape));
+            }
+            if schema.is_some() {
+                lines.push("    }".to_string());
+            }
+        }
+        for edge in &self.edges {
+            lines.push(format!(
+                "    {} -> {};",
+                dot_id(&edge.from),
+                dot_id(&edge.to)
+            ));
+        }
+        lines.push("}".to_string());
+        lines.join("\n") + "\n"
+    }
+
+    /// Render as a Mermaid flowchart with a subgraph per schema. Models are
+    /// rectangles and sources cylinders.
+    pub fn to_mermaid(&self) -> String {
+        // Mermaid ids can't contain dots, so nodes are numbered and labelled with their name
+        let ids: BTreeMap<&str, String> = self
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(index, node)| (node.id.as_str(), format!("n{}", index)))
+            .collect();
+
+        let mut lines = vec!["flowchart LR".to_string()];
+        for (index, (schema, nodes)) in self.nodes_by_schema().into_iter().enumerate() {
+            let indent = if schema.is_some() { "        " } else { "    " };
+            if let Some(schema) = schema {
+                lines.push(format!(
+                    "    subgraph s{} [{}]",
+                    index,
+                    mermaid_label(schema)
+                ));
+            }
+            for node in nodes {
+                let label = mermaid_label(&node.id);
+                let shape = match node.node_type {
+                    NodeType::Model => format!("[{}]", label),
+                    NodeType::Source => format!("[({})]", label),
+                };
+                lines.push(format!("{}{}{}", indent, ids[node.id.as_str()], shape));
+            }
+            if schema.is_some() {
+                lines.push("    end".to_string());
+            }
+        }
+        for edge in &self.edges {
+            lines.push(format!(
+                "    {} --> {}",
+                ids[edge.from.as_str()],
+                ids[edge.to.as_str()]
+            ));
+        }
+        lines.join("\n") + "\n"
+    }
+
+    /// Render as a JSON object of nodes and edges
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).wrap_err("Failed to serialize graph")
+    }
+}
+
+/// Schema a source id is qualified with, e.g. `raw` for `raw.orders` or `warehouse.raw.orders`
+fn source_schema(source_id: &str) -> Option<String> {
+    let mut parts = source_id.rsplit('.');
+    parts.next();
+    parts.next().map(str::to_string)
+}
+
+/// Quote an identifier for DOT
+fn dot_id(id: &str) -> String {
+    format!("\"{}\"", id.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Quote a label for Mermaid, which escapes quotes as entities
+fn mermaid_label(label: &str) -> String {
+    format!("\"{}\"", label.replace('"', "#quot;"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ProjectConfig;
+    use crate::parser::{ModelMetadata, SourceReference};
+    use std::path::PathBuf;
+
+    fn node(id: &str, node_type: NodeType, schema: Option<&str>) -> GraphNode {
+        GraphNode {
+            id: id.to_string(),
+            node_type,
+            schema: schema.map(str::to_string),
+            materialized: None,
+            path: None,
+        }
+    }
+
+    fn edge(from: &str, to: &str) -> GraphEdge {
+        GraphEdge {
+            from: from.to_string(),
+            to: to.to_string(),
+        }
+    }
+
+    /// raw_users (unqualified source) -> bronze.users -> gold.orders <- raw.orders
+    fn graph() -> LineageGraph {
+        LineageGraph {
+            nodes: vec![
+                node("bronze.users", NodeType::Model, Some("bronze")),
+                node("gold.orders", NodeType::Model, Some("gold")),
+                node("raw.orders", NodeType::Source, Some("raw")),
+                node("raw_users", NodeType::Source, None),
+            ],
+            edges: vec![
+                edge("bronze.users", "gold.orders"),
+                edge("raw.orders", "gold.orders"),
+                edge("raw_users", "bronze.users"),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_lineage_graph_from_catalog() -> Result<()> {
+        // gold.orders also reads raw.customers in a subquery, which only shows up in
+        // its references, and summarizes it in a CTE
+        let mut orders = ModelMetadata::selecting_from(
+            "gold.orders",
+            &["silver.customers", "raw.orders", "summary"],
+        );
+        orders.references.push(SourceReference {
+            source_id: "raw.customers".to_string(),
+            byte_range: 0..0,
+            line: 3,
+        });
+        orders.cte_names = vec!["summary".to_string()];
+        let models = [
+            ModelMetadata::selecting_from("bronze.users", &["raw.users"]),
+            ModelMetadata::selecting_from("silver.customers", &["bronze.users"]),
+            orders,
+        ];
+        let mut catalog = ModelCatalog::from_models(ProjectConfig::default(), models)?;
+        catalog.build_dependency_graph()?;
+
+        // Only edges between selected models are kept, sources come along with their models
+        let selected = BTreeSet::from(["gold.orders".to_string(), "silver.customers".to_string()]);
+        let graph = LineageGraph::new(&catalog, &selected)?;
+
+        let nodes: Vec<(&str, NodeType, Option<&str>)> = graph
+            .nodes
+            .iter()
+            .map(|node| (node.id.as_str(), node.node_type, node.schema.as_deref()))
+            .collect();
+        assert_eq!(
+            nodes,
+            vec![
+                ("gold.orders", NodeType::Model, Some("gold")),
+                ("raw.customers", NodeType::Source, Some("raw")),
+                ("raw.orders", NodeType::Source, Some("raw")),
+                ("silver.customers", NodeType::Model, Some("silver")),
+            ]
+        );
+        assert_eq!(
+            graph.edges,
+            vec![
+                edge("raw.customers", "gold.orders"),
+                edge("raw.orders", "gold.orders"),
+                edge("silver.customers", "gold.orders"),
+            ]
+        );
+        assert_eq!(graph.nodes[0].materialized.as_deref(), Some("table"));
+        assert_eq!(
+            graph.nodes[0].path,
+            Some(
+                PathBuf::from("models/gold/orders.sql")
+                    .display()
+                    .to_string()
+            )
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_graph_to_dot() {
+        assert_eq!(
+            graph().to_dot(),
+            r#"digraph cadac {
+    rankdir=LR;
+    "raw_users" [shape=cylinder, style=dashed];
+    subgraph "cluster_bronze" {
+        label="bronze";
+        "bronze.users" [shape=box];
+    }
+    subgraph "cluster_gold" {
+        label="gold";
+        "gold.orders" [shape=box];
+    }
+    subgraph "cluster_raw" {
+        label="raw";
+        "raw.orders" [shape=cylinder, style=dashed];
+    }
+    "bronze.users" -> "gold.orders";
+    "raw.orders" -> "gold.orders";
+    "raw_users" -> "bronze.users";
+}
+"#
+        );
+    }
+
+    #[test]
+    fn test_graph_to_mermaid() {
+        assert_eq!(
+            graph().to_mermaid(),
+            r#"flowchart LR
+    n3[("raw_users")]
+    subgraph s1 ["bronze"]
+        n0["bronze.users"]
+    end
+    subgraph s2 ["gold"]
+        n1["gold.orders"]
+    end
+    subgraph s3 ["raw"]
+        n2[("raw.orders")]
+    end
+    n0 --> n1
+    n2 --> n1
+    n3 --> n0
+"#
+        );
+    }
+
+    #[test]
+    fn test_graph_to_json() -> Result<()> {
+        let json: serde_json::Value = serde_json::from_str(&graph().to_json()?)?;
+
+        assert_eq!(json["nodes"][0]["id"], "bronze.users");
+        assert_eq!(json["nodes"][0]["type"], "model");
+        assert_eq!(json["nodes"][0]["schema"], "bronze");
+        assert_eq!(json["nodes"][3]["type"], "source");
+        assert_eq!(json["nodes"][3]["schema"], serde_json::Value::Null);
+        assert_eq!(
+            json["edges"][2],
+            serde_json::json!({"from": "raw_users", "to": "bronze.users"})
+        );
+
+        Ok(())
+    }
+}
@@ -1,18 +1,35 @@
-use args::{BaseCliArgs, Commands};
+use args::{BaseCliArgs, Commands, GraphFormat, ProjectArgs, SelectionArgs};
+#[cfg(any(feature = "postgres", feature = "databricks", feature = "snowflake"))]
+use args::ConnectionArgs;
 use clap::Parser;
 use cli::main_cli;
 use color_eyre::Result;
+use color_eyre::eyre::Context;
+use config::ProjectConfig;
 use discovery::ModelCatalog;
-use execution::{create_engine_with_available_adapters, SqlDialect};
+use execution::SqlDialect;
+#[cfg(any(feature = "postgres", feature = "databricks", feature = "snowflake"))]
+use execution::{create_engine_with_available_adapters, RetryPolicy, RunOptions};
 use parser::{ModelMetadata, ModelParser};
+#[cfg(any(feature = "postgres", feature = "databricks", feature = "snowflake"))]
+use parser::Materialization;
+use profiles::TargetProfile;
 use std::fs;
+use std::path::Path;
 
 mod args;
 mod cli;
+mod compiler;
+mod config;
 mod dependency_graph;
 mod discovery;
 mod execution;
+mod graph_export;
 mod parser;
+mod profiles;
+#[cfg(any(feature = "postgres", feature = "databricks", feature = "snowflake"))]
+mod run_results;
+mod selector;
 
 #[cfg(test)]
 mod discovery_test;
@@ -50,32 +67,47 @@ async fn run_cli() -> Result<()> {
         Commands::Parse { file } => {
             parse_sql_file(file)?;
         }
-        Commands::Discover { model_path } => {
-            discover_models(model_path)?;
+        Commands::Discover { project } => {
+            let config = load_project_config(&project)?;
+            discover_models(config.model_path(project.model_path.clone()), config)?;
         }
         Commands::Tui => {
             main_cli()?;
         }
         Commands::Run {
-            model_path,
-            model_name,
-            upstream,
-            downstream,
+            project,
+            selection,
             dry_run,
             fail_fast,
+            full_refresh,
+            retry_failed,
+            threads,
+            retries,
+            timeout,
             connection,
         } => {
             #[cfg(any(feature = "postgres", feature = "databricks", feature = "snowflake"))]
             {
-                run_models(
-                    model_path,
-                    model_name,
-                    upstream,
-                    downstream,
+                let config = load_project_config(&project)?;
+                let model_path = config.model_path(project.model_path.clone());
+                let target = resolve_target(&config, connection)?;
+                let options = RunOptions {
+                    select: selection.selectors(),
+                    exclude: selection.exclude,
                     dry_run,
                     fail_fast,
-                    connection,
-                ).await?;
+                    full_refresh,
+                    snapshot: false,
+                    retry_failed,
+                    target_database: None,
+                    threads: threads.or(target.threads).unwrap_or(1),
+                    retry: RetryPolicy {
+                        retries: retries.or(target.retries).unwrap_or(0),
+                        ..RetryPolicy::default()
+                    },
+                    timeout,
+                };
+                run_models(model_path, target, options, config).await?;
             }
             
             #[cfg(not(any(feature = "postgres", feature = "databricks", feature = "snowflake")))]
@@ -87,11 +119,73 @@ async fn run_cli() -> Result<()> {
                 ));
             }
         }
+        Commands::Compile { project, selection, target } => {
+            let config = load_project_config(&project)?;
+            compile_models(config.model_path(project.model_path.clone()), config, &selection, target.as_deref())?;
+        }
+        Commands::List { project, selection } => {
+            let config = load_project_config(&project)?;
+            list_models(config.model_path(project.model_path.clone()), config, &selection)?;
+        }
+        Commands::Graph { project, selection, format, output } => {
+            let config = load_project_config(&project)?;
+            export_graph(config.model_path(project.model_path.clone()), config, &selection, format, output)?;
+        }
+        Commands::Snapshot {
+            project,
+            selection,
+            dry_run,
+            fail_fast,
+            threads,
+            retries,
+            timeout,
+            connection,
+        } => {
+            let config = load_project_config(&project)?;
+            let model_path = config.model_path(project.model_path.clone());
+            let target = resolve_target(&config, connection)?;
+            let options = RunOptions {
+                select: selection.selectors(),
+                exclude: selection.exclude,
+                dry_run,
+                fail_fast,
+                snapshot: true,
+                threads: threads.or(target.threads).unwrap_or(1),
+                retry: RetryPolicy {
+                    retries: retries.or(target.retries).unwrap_or(0),
+                    ..RetryPolicy::default()
+                },
+                timeout,
+                ..RunOptions::default()
+            };
+            run_models(model_path, target, options, config).await?;
+        }
     }
 
     Ok(())
 }
 
+/// Load cadac.toml from the current directory and apply the CLI overrides
+fn load_project_config(project: &ProjectArgs) -> Result<ProjectConfig> {
+    let mut config = ProjectConfig::load(Path::new("."))?;
+    config.apply_cli_vars(&project.vars)?;
+    Ok(config)
+}
+
+/// Resolve the target to run against. An explicit `--connection` wins, then
+/// the named or default target from profiles.toml, then the connection in
+/// cadac.toml.
+#[cfg(any(feature = "postgres", feature = "databricks", feature = "snowflake"))]
+fn resolve_target(config: &ProjectConfig, connection: ConnectionArgs) -> Result<TargetProfile> {
+    if let Some(connection_string) = connection.connection {
+        return TargetProfile::from_connection_string(&connection_string);
+    }
+
+    profiles::configured_target(Path::new("."), config, connection.target.as_deref())?.ok_or_else(|| {
+        color_eyre::eyre::eyre!("No database connection given. Pass --connection or --target, or set project.connection in cadac.toml")
+    })
+}
+
 fn parse_sql_file(file_path: std::path::PathBuf) -> Result<()> {
     // Read the SQL file
     let sql_content = fs::read_to_string(&file_path)?;
@@ -114,6 +208,14 @@ fn parse_sql_file(file_path: std::path::PathBuf) -> Result<()> {
         println!("📝 Description: {}", description);
     }
 
+    if let Some(materialization) = &model.config.materialized {
+        println!("🧱 Materialized as: {}", materialization);
+    }
+
+    if !model.config.tags.is_empty() {
+        println!("🏷️  Tags: {}", model.config.tags.join(", "));
+    }
+
     println!("\n📊 Sources ({}):", model.sources.len());
     for source in &model.sources {
         println!("  • {} ({})", source.name, source.id);
@@ -134,10 +236,10 @@ fn parse_sql_file(file_path: std::path::PathBuf) -> Result<()> {
     Ok(())
 }
 
-fn discover_models(model_path: std::path::PathBuf) -> Result<()> {
+fn discover_models(model_path: std::path::PathBuf, config: ProjectConfig) -> Result<()> {
     println!("🔍 Discovering models in: {}", model_path.display());
 
-    let mut catalog = ModelCatalog::new(model_path);
+    let mut catalog = ModelCatalog::new(model_path).with_config(config);
     catalog.discover_models()?;
 
     println!("📚 Found {} models:", catalog.models.len());
@@ -149,6 +251,13 @@ fn discover_models(model_path: std::path::PathBuf) -> Result<()> {
             println!("   📝 {}", description);
         }
 
+        println!("   🧱 Materialized as: {}", catalog.get_materialization(name));
+
+        let tags = catalog.get_tags(name);
+        if !tags.is_empty() {
+            println!("   🏷️  Tags: {}", tags.join(", "));
+        }
+
         let source_names: Vec<String> = model.sources.iter().map(|s| s.id.clone()).collect();
         println!("   📊 Sources: {}", source_names.join(", "));
 
@@ -170,6 +279,9 @@ fn discover_models(model_path: std::path::PathBuf) -> Result<()> {
     // Check for circular dependencies
     if catalog.has_circular_dependencies() {
         println!("   ⚠️  Circular dependencies detected!");
+        for line in catalog.describe_cycles().lines() {
+            println!("      {}", line);
+        }
     } else {
         println!("   ✅ No circular dependencies");
     }
@@ -203,6 +315,81 @@ fn discover_models(model_path: std::path::PathBuf) -> Result<()> {
         if dependencies.is_empty() && dependents.is_empty() {
             println!("      🔸 No internal dependencies");
         }
+
+        let graph = &catalog.dependency_graph;
+        let indirect_upstream = indirect_paths(graph.get_upstream_paths(model_name, None));
+        if !indirect_upstream.is_empty() {
+            println!("      ⏮️  Indirectly depends on: {}", indirect_upstream.join(", "));
+        }
+        let indirect_downstream = indirect_paths(graph.get_downstream_paths(model_name, None));
+        if !indirect_downstream.is_empty() {
+            println!("      ⏭️  Impacts: {}", indirect_downstream.join(", "));
+        }
+    }
+
+    Ok(())
+}
+
+/// Describe models reached through other models as `model (via a -> b)`
+fn indirect_paths(paths: Vec<dependency_graph::ModelPath>) -> Vec<String> {
+    paths
+        .iter()
+        .filter(|path| path.depth() > 1)
+        .map(|path| format!("{} (via {})", path.model, path.via().join(" -> ")))
+        .collect()
+}
+
+/// Print the selected models in execution order, one per line
+fn list_models(model_path: std::path::PathBuf, config: ProjectConfig, selection: &SelectionArgs) -> Result<()> {
+    let mut catalog = ModelCatalog::new(model_path).with_config(config);
+    catalog.discover_models()?;
+    catalog.build_dependency_graph()?;
+
+    let selected = selector::select_models(&catalog, &selection.selectors(), &selection.exclude)?;
+    for model_name in catalog.dependency_graph.topological_order()? {
+        if selected.contains(&model_name) {
+            println!("{}", model_name);
+        }
+    }
+
+    Ok(())
+}
+
+/// Export the graph of the selected models to a file, or print it
+fn export_graph(
+    model_path: std::path::PathBuf,
+    config: ProjectConfig,
+    selection: &SelectionArgs,
+    format: GraphFormat,
+    output: Option<std::path::PathBuf>,
+) -> Result<()> {
+    let mut catalog = ModelCatalog::new(model_path).with_config(config);
+    catalog.discover_models()?;
+    catalog.build_dependency_graph()?;
+
+    let selected = selector::select_models(&catalog, &selection.selectors(), &selection.exclude)?;
+    let graph = graph_export::LineageGraph::new(&catalog, &selected)?;
+    let rendered = match format {
+        GraphFormat::Dot => graph.to_dot(),
+        GraphFormat::Mermaid => graph.to_mermaid(),
+        GraphFormat::Json => graph.to_json()?,
+    };
+
+    match output {
+        Some(output) => {
+            if let Some(parent) = output.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+                fs::create_dir_all(parent)
+                    .wrap_err_with(|| format!("Failed to create directory: {:?}", parent))?;
+            }
+            fs::write(&output, rendered)
+                .wrap_err_with(|| format!("Failed to write graph: {:?}", output))?;
+            println!(
+                "📈 Wrote graph of {} models to {}",
+                selected.len(),
+                output.display()
+            );
+        }
+        None => print!("{}", rendered),
     }
 
     Ok(())
@@ -218,13 +405,26 @@ fn run_cli_sync() -> Result<()> {
         Commands::Parse { file } => {
             parse_sql_file(file)?;
         }
-        Commands::Discover { model_path } => {
-            discover_models(model_path)?;
+        Commands::Discover { project } => {
+            let config = load_project_config(&project)?;
+            discover_models(config.model_path(project.model_path.clone()), config)?;
         }
         Commands::Tui => {
             main_cli()?;
         }
-        Commands::Run { .. } => {
+        Commands::List { project, selection } => {
+            let config = load_project_config(&project)?;
+            list_models(config.model_path(project.model_path.clone()), config, &selection)?;
+        }
+        Commands::Graph { project, selection, format, output } => {
+            let config = load_project_config(&project)?;
+            export_graph(config.model_path(project.model_path.clone()), config, &selection, format, output)?;
+        }
+        Commands::Compile { project, selection, target } => {
+            let config = load_project_config(&project)?;
+            compile_models(config.model_path(project.model_path.clone()), config, &selection, target.as_deref())?;
+        }
+        Commands::Run { .. } | Commands::Snapshot { .. } => {
             return Err(color_eyre::eyre::eyre!(
                 "No database adapters available. Please install CADAC with database support:\n\
                 - For PostgreSQL: cargo install cadac --features postgres\n\
@@ -236,20 +436,78 @@ fn run_cli_sync() -> Result<()> {
     Ok(())
 }
 
+/// Discover the models of a project and build their dependency graph,
+/// naming relations with the target's schema prefix and database
+fn load_catalog(
+    model_path: std::path::PathBuf,
+    config: ProjectConfig,
+    schema_prefix: Option<String>,
+    database: Option<String>,
+) -> Result<ModelCatalog> {
+    let mut catalog = ModelCatalog::new(model_path)
+        .with_config(config)
+        .with_schema_prefix(schema_prefix)
+        .with_database(database);
+    catalog.discover_models()?;
+    catalog.build_dependency_graph()?;
+    
+    println!("📚 Found {} models", catalog.models.len());
+    
+    // Check for circular dependencies
+    if catalog.has_circular_dependencies() {
+        return Err(color_eyre::eyre::eyre!(
+            "Circular dependencies detected! Cannot execute models.\n{}",
+            catalog.describe_cycles()
+        ));
+    }
+    
+    Ok(catalog)
+}
+
+/// Write the SQL of the selected models for the configured target, or for
+/// PostgreSQL when no target is configured. No database is connected to.
+fn compile_models(
+    model_path: std::path::PathBuf,
+    config: ProjectConfig,
+    selection: &SelectionArgs,
+    target_name: Option<&str>,
+) -> Result<()> {
+    println!("🛠️  Compiling models from: {}", model_path.display());
+    
+    let target = profiles::configured_target(Path::new("."), &config, target_name)?;
+    let dialect = target.as_ref().map_or(SqlDialect::Postgres, |target| target.dialect.clone());
+    let schema_prefix = target.as_ref().and_then(|target| target.schema_prefix.clone());
+    let database = target.as_ref().and_then(TargetProfile::relation_database);
+    let catalog = load_catalog(model_path, config, schema_prefix, database)?;
+    let selected = selector::select_models(&catalog, &selection.selectors(), &selection.exclude)?;
+    
+    let output_dir = Path::new(compiler::COMPILED_DIR);
+    let written = compiler::write_compiled_models(&catalog, &selected, &dialect, output_dir)?;
+    for path in &written {
+        println!("  📝 {}", path.display());
+    }
+    
+    let compiled_for = match &target {
+        Some(target) => format!("target {}", target.name),
+        None => "PostgreSQL, as no target is configured".to_string(),
+    };
+    println!("\n✅ Compiled {} models into {} for {}", written.len(), output_dir.display(), compiled_for);
+    
+    Ok(())
+}
+
 #[cfg(any(feature = "postgres", feature = "databricks", feature = "snowflake"))]
 async fn run_models(
     model_path: std::path::PathBuf,
-    model_name: Option<String>,
-    upstream: bool,
-    downstream: bool,
-    dry_run: bool,
-    fail_fast: bool,
-    connection: String,
+    target: TargetProfile,
+    options: RunOptions,
+    config: ProjectConfig,
 ) -> Result<()> {
     println!("🚀 Running models from: {}", model_path.display());
+    let run_started = std::time::Instant::now();
     
     // Create execution engine with available adapters
-    let engine = create_engine_with_available_adapters();
+    let mut engine = create_engine_with_available_adapters();
     
     // Check if any database adapters are available
     let available_dialects = engine.available_dialects();
@@ -264,65 +522,62 @@ async fn run_models(
     println!("📊 Available database adapters: {:?}", available_dialects);
     
     // Discover models and build dependency graph
-    let mut catalog = ModelCatalog::new(model_path);
-    catalog.discover_models()?;
-    catalog.build_dependency_graph()?;
-    
-    println!("📚 Found {} models", catalog.models.len());
-    
-    // Check for circular dependencies
-    if catalog.has_circular_dependencies() {
-        return Err(color_eyre::eyre::eyre!("Circular dependencies detected! Cannot execute models."));
-    }
+    let database = options.target_database.clone().or_else(|| target.relation_database());
+    let catalog = load_catalog(model_path, config, target.schema_prefix.clone(), database)?;
     
     // Determine which models to run
-    let models_to_run = if let Some(specific_model) = model_name {
-        let mut models = vec![specific_model.clone()];
-        
-        if upstream {
-            let deps = catalog.get_dependencies(&specific_model);
-            models.extend(deps);
-        }
-        
-        if downstream {
-            let dependents = catalog.get_dependents(&specific_model);
-            models.extend(dependents);
-        }
-        
-        models.sort();
-        models.dedup();
-        models
+    let mut models_to_run: std::collections::BTreeSet<String> = if options.retry_failed {
+        let command = run_results::command(&options);
+        let previous = run_results::PreviousRun::load(&run_results::results_file(command), command)?;
+        let models = previous.models_to_retry(&catalog.dependency_graph);
+        if models.is_empty() {
+            println!("\n✅ Every model succeeded in the previous run. Nothing to retry.");
+            return Ok(());
+        }
+        println!("🔁 Retrying {} model(s) that didn't succeed in the previous run, with their dependents", models.len());
+        models.into_iter().collect()
     } else {
-        // Run all models
-        catalog.models.keys().cloned().collect()
+        selector::select_models(&catalog, &options.select, &options.exclude)?
     };
     
+    // Replacing a relation drops the views of the models built on it, so they are rebuilt too
+    if !options.snapshot {
+        let dependent_views: Vec<String> = models_to_run
+            .iter()
+            .filter(|model| catalog.get_materialization(model) != Materialization::Snapshot)
+            .flat_map(|model| catalog.get_dependent_views(model))
+            .filter(|view| !models_to_run.contains(view))
+            .collect::<std::collections::BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        if !dependent_views.is_empty() {
+            println!("➕ Also rebuilding {} view(s) built on the selected models: {}", dependent_views.len(), dependent_views.join(", "));
+            models_to_run.extend(dependent_views);
+        }
+    }
+    
     // Get execution order
     let execution_order = catalog.get_execution_order()?;
     let filtered_execution_order: Vec<String> = execution_order
         .into_iter()
         .filter(|model| models_to_run.contains(model))
+        .filter(|model| (catalog.get_materialization(model) == Materialization::Snapshot) == options.snapshot)
         .collect();
     
     println!("\n📋 Execution Plan:");
     for (i, model) in filtered_execution_order.iter().enumerate() {
-        println!("  {}. {}", i + 1, model);
+        println!("  {}. {} ({})", i + 1, model, catalog.get_materialization(model));
     }
     
-    if dry_run {
+    if options.dry_run {
         println!("\n🔍 Dry run completed. No models were executed.");
         return Ok(());
     }
     
-    // Determine dialect from connection string
-    let dialect = if connection.starts_with("postgresql://") || connection.starts_with("postgres://") {
-        SqlDialect::Postgres
-    } else {
-        return Err(color_eyre::eyre::eyre!(
-            "Cannot determine database dialect from connection string. Supported prefixes:\n\
-            - PostgreSQL: postgresql:// or postgres://"
-        ));
-    };
+    // The target declares which adapter to use
+    let dialect = target.dialect.clone();
+    let connection = target.connection_string(|name| std::env::var(name).ok())?;
+    println!("\n🎯 Target: {} ({:?})", target.name, dialect);
     
     // Check if the required dialect is supported
     if !engine.supports_dialect(&dialect) {
@@ -333,55 +588,131 @@ async fn run_models(
         ));
     }
     
-    // Execute models
-    let mut success_count = 0;
-    let mut failed_count = 0;
+    // Compile every model up front, pointing model references at their
+    // physical relations and inlining ephemeral dependencies
+    let models_to_execute: Vec<String> = filtered_execution_order
+        .iter()
+        .filter(|model| catalog.model_identities.contains_key(*model))
+        .cloned()
+        .collect();
+    let mut builds = std::collections::HashMap::new();
+    for model_name in &models_to_execute {
+        builds.insert(model_name.clone(), compiler::model_build(&catalog, model_name, options.full_refresh)?);
+    }
     
-    println!("\n🔄 Executing models...");
+    // A model waits only for the executed models it selects from
+    let dependencies: std::collections::HashMap<String, Vec<String>> = models_to_execute
+        .iter()
+        .map(|model| (model.clone(), execution::scheduler::execution_dependencies(&catalog.dependency_graph, model)))
+        .collect();
     
-    for model_name in &filtered_execution_order {
-        if let Some(model_identity) = catalog.model_identities.get(model_name) {
-            println!("\n📄 Executing: {}", model_name);
-            
-            // Read the SQL file content
-            let sql_content = std::fs::read_to_string(&model_identity.file_path)?;
-            
-            match engine.execute_sql(&sql_content, &connection, dialect.clone()).await {
-                Ok(result) => {
-                    match result.status {
-                        execution::ExecutionStatus::Success => {
-                            println!("  ✅ Success - {} rows affected in {:?}", 
-                                result.rows_affected, result.execution_time);
-                            success_count += 1;
-                        }
-                        execution::ExecutionStatus::Failed => {
-                            println!("  ❌ Failed - {}", 
-                                result.message.unwrap_or_else(|| "Unknown error".to_string()));
-                            failed_count += 1;
-                            if fail_fast {
-                                return Err(color_eyre::eyre::eyre!("Model execution failed: {}", model_name));
-                            }
-                        }
-                        execution::ExecutionStatus::Skipped => {
-                            println!("  ⏭️  Skipped");
-                        }
-                    }
+    println!("\n🔄 Executing models with {} thread(s)...", options.threads);
+    
+    // Ctrl-C cancels the models that are running instead of killing the process,
+    // so their transactions are rolled back on the server
+    let (cancel_sender, cancel_receiver) = tokio::sync::watch::channel(false);
+    let ctrl_c = tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            println!("\n🛑 Cancelling running models...");
+            let _ = cancel_sender.send(true);
+        }
+    });
+    
+    // Keep one connection per thread open for the whole run
+    engine.set_pool_size(options.threads);
+    let engine = std::sync::Arc::new(engine);
+    let outcomes = execution::scheduler::run_dag(
+        &models_to_execute,
+        &dependencies,
+        options.threads,
+        options.fail_fast,
+        |model_name| {
+            println!("  📄 Started: {}", model_name);
+            let engine = engine.clone();
+            let build = builds.remove(&model_name).expect("every scheduled model is compiled");
+            let connection = connection.clone();
+            let dialect = dialect.clone();
+            let retry = options.retry.clone();
+            let timeout = build.config.timeout.or(options.timeout);
+            let cancelled = cancel_receiver.clone();
+            async move {
+                if *cancelled.borrow() {
+                    return Ok(execution::ExecutionResult::new(execution::ExecutionStatus::Skipped)
+                        .with_message("Skipped because the run was cancelled".to_string()));
                 }
-                Err(e) => {
-                    println!("  ❌ Error: {}", e);
-                    failed_count += 1;
-                    if fail_fast {
-                        return Err(e);
+                let result = execution::scheduler::run_with_retry(&retry, || {
+                    let interrupt = execution::scheduler::wait_for_interruption(timeout, cancelled.clone());
+                    engine.materialize(&build, &connection, dialect.clone(), interrupt)
+                }).await;
+                match &result {
+                    Ok(result) if result.status == execution::ExecutionStatus::Success => {
+                        println!("  ✅ {} - materialized {} as {} ({} rows affected) in {:?}{}", 
+                            model_name, build.relation, build.materialization, result.rows_affected, result.execution_time, attempts_note(result));
+                    }
+                    Ok(result) if result.status == execution::ExecutionStatus::Failed => {
+                        println!("  ❌ {} failed - {}{}", 
+                            model_name, failure_message(result), attempts_note(result));
                     }
+                    Ok(_) => println!("  ⏭️  {} skipped", model_name),
+                    Err(e) => println!("  ❌ {} error: {}", model_name, e),
                 }
+                result
+            }
+        },
+    ).await?;
+    ctrl_c.abort();
+    let cancelled = *cancel_receiver.borrow();
+    engine.close_connections().await?;
+    
+    // Summarize in execution order so the output doesn't depend on timing
+    let mut success_count = 0;
+    let mut failed_count = 0;
+    let mut skipped_count = 0;
+    let mut not_run_count = 0;
+    
+    println!("\n📊 Execution Summary:");
+    for outcome in &outcomes {
+        match &outcome.result {
+            Some(Ok(result)) if result.status == execution::ExecutionStatus::Success => {
+                println!("  ✅ {} ({:?})", outcome.model, result.execution_time);
+                success_count += 1;
+            }
+            Some(Ok(result)) if result.status == execution::ExecutionStatus::Failed => {
+                println!("  ❌ {} - {}", outcome.model, failure_message(result));
+                failed_count += 1;
+            }
+            Some(Ok(result)) => {
+                println!("  ⏭️  {} - {}", outcome.model, result.message.as_deref().unwrap_or("Skipped"));
+                skipped_count += 1;
+            }
+            Some(Err(e)) => {
+                println!("  ❌ {} - {}", outcome.model, e);
+                failed_count += 1;
+            }
+            None => {
+                println!("  ⏹️  {} - not run after an earlier failure", outcome.model);
+                not_run_count += 1;
             }
         }
     }
     
-    println!("\n📊 Execution Summary:");
-    println!("  ✅ Successful: {}", success_count);
+    println!("\n  ✅ Successful: {}", success_count);
     println!("  ❌ Failed: {}", failed_count);
-    println!("  📋 Total: {}", filtered_execution_order.len());
+    println!("  ⏭️  Skipped: {}", skipped_count);
+    if not_run_count > 0 {
+        println!("  ⏹️  Not run: {}", not_run_count);
+    }
+    println!("  📋 Total: {}", outcomes.len());
+    
+    // Record every model's result for orchestration and alerting
+    let run_results = run_results::RunResults::new(&options, &target, run_started.elapsed(), outcomes);
+    let run_results_path = run_results::results_file(run_results::command(&options));
+    run_results.write(&run_results_path)?;
+    println!("\n📝 Wrote run results to {}", run_results_path.display());
+    
+    if cancelled {
+        return Err(color_eyre::eyre::eyre!("Run cancelled"));
+    }
     
     if failed_count > 0 {
         return Err(color_eyre::eyre::eyre!("{} model(s) failed to execute", failed_count));
@@ -389,3 +720,23 @@ async fn run_models(
     
     Ok(())
 }
+
+/// Describe why a model failed, preferring the structured error the adapter reported
+#[cfg(any(feature = "postgres", feature = "databricks", feature = "snowflake"))]
+fn failure_message(result: &execution::ExecutionResult) -> String {
+    match (&result.error, &result.message) {
+        (Some(error), _) => error.to_string(),
+        (None, Some(message)) => message.clone(),
+        (None, None) => "Unknown error".to_string(),
+    }
+}
+
+/// Note appended to a model's result line when it needed retries
+#[cfg(any(feature = "postgres", feature = "databricks", feature = "snowflake"))]
+fn attempts_note(result: &execution::ExecutionResult) -> String {
+    if result.attempts > 1 {
+        format!(" after {} attempts", result.attempts)
+    } else {
+        String::new()
+    }
+}
@@ -1,3 +1,6 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::Duration;
 use tree_sitter::{Node, Parser};
 
 const NODE_KIND_SOURCE_FILE: &str = "source_file";
@@ -13,11 +16,29 @@ const NODE_KIND_JOIN: &str = "join";
 const NODE_KIND_COLUMN_REFERENCE: &str = "column_reference";
 const NODE_KIND_SELECT_LIST: &str = "select_list";
 const NODE_KIND_SELECT_LIST_ITEM: &str = "select_list_item";
+const NODE_KIND_CTE: &str = "cte";
+
+/// Prefix of header comments that configure the model instead of describing it
+const CONFIG_DIRECTIVE_PREFIX: &str = "cadac:";
 
 #[derive(Debug, PartialEq)]
 pub enum ModelParseError {
     ParseError(String),
     MultipleStatements(usize),
+    /// A config directive entry that is not of the form `key=value`
+    MalformedConfigEntry { entry: String, line: usize },
+    /// A config directive key that cadac does not know
+    UnknownConfigKey { key: String, line: usize },
+    /// A config directive value that is not valid for its key
+    InvalidConfigValue {
+        key: String,
+        value: String,
+        reason: String,
+        line: usize,
+    },
+    /// Config directives that are valid on their own but conflict with each other,
+    /// reported on the line of the directive that needs the missing setting
+    ConflictingConfig { reason: String, line: usize },
 }
 
 impl std::fmt::Display for ModelParseError {
@@ -29,6 +50,27 @@ impl std::fmt::Display for ModelParseError {
                 count
             ),
             ModelParseError::ParseError(msg) => write!(f, "Parse error: {}", msg),
+            ModelParseError::MalformedConfigEntry { entry, line } => write!(
+                f,
+                "Malformed config entry '{}' on line {}, expected key=value",
+                entry, line
+            ),
+            ModelParseError::UnknownConfigKey { key, line } => {
+                write!(f, "Unknown config key '{}' on line {}", key, line)
+            }
+            ModelParseError::InvalidConfigValue {
+                key,
+                value,
+                reason,
+                line,
+            } => write!(
+                f,
+                "Invalid value '{}' for config key '{}' on line {}: {}",
+                value, key, line, reason
+            ),
+            ModelParseError::ConflictingConfig { reason, line } => {
+                write!(f, "Invalid config on line {}: {}", line, reason)
+            }
         }
     }
 }
@@ -44,6 +86,17 @@ pub struct Source {
     pub schema: Option<String>,
 }
 
+/// Location of a table reference in the model SQL, used to rewrite it at compile time
+#[derive(Debug, PartialEq, Clone)]
+pub struct SourceReference {
+    /// Id of the referenced source
+    pub source_id: String,
+    /// Byte range of the reference, e.g. `schema.table`, excluding any alias
+    pub byte_range: std::ops::Range<usize>,
+    /// 1-based line of the reference
+    pub line: usize,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct Column {
     pub name: String,
@@ -54,12 +107,149 @@ pub struct Column {
     pub sources: Vec<String>,
 }
 
+/// How a model is persisted in the target database
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Materialization {
+    Table,
+    View,
+    Incremental,
+    Snapshot,
+    /// Never built; inlined as a CTE into every dependent model
+    Ephemeral,
+}
+
+impl std::str::FromStr for Materialization {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "table" => Ok(Materialization::Table),
+            "view" => Ok(Materialization::View),
+            "incremental" => Ok(Materialization::Incremental),
+            "snapshot" => Ok(Materialization::Snapshot),
+            "ephemeral" => Ok(Materialization::Ephemeral),
+            _ => Err(format!(
+                "Unknown materialization '{}', expected one of: table, view, incremental, snapshot, ephemeral",
+                value
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for Materialization {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Materialization::Table => write!(f, "table"),
+            Materialization::View => write!(f, "view"),
+            Materialization::Incremental => write!(f, "incremental"),
+            Materialization::Snapshot => write!(f, "snapshot"),
+            Materialization::Ephemeral => write!(f, "ephemeral"),
+        }
+    }
+}
+
+/// How new rows of an incremental model are applied to the existing table
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum IncrementalStrategy {
+    /// Insert new rows as-is
+    Append,
+    /// Update rows matching the unique key and insert the rest
+    Merge,
+    /// Delete rows matching the unique key, then insert all new rows
+    DeleteInsert,
+}
+
+impl std::str::FromStr for IncrementalStrategy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "append" => Ok(IncrementalStrategy::Append),
+            "merge" => Ok(IncrementalStrategy::Merge),
+            "delete+insert" => Ok(IncrementalStrategy::DeleteInsert),
+            _ => Err(format!(
+                "Unknown incremental strategy '{}', expected one of: append, merge, delete+insert",
+                value
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for IncrementalStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IncrementalStrategy::Append => write!(f, "append"),
+            IncrementalStrategy::Merge => write!(f, "merge"),
+            IncrementalStrategy::DeleteInsert => write!(f, "delete+insert"),
+        }
+    }
+}
+
+/// How a snapshot detects that a row changed
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum SnapshotStrategy {
+    /// A row changed when its timestamp column moved forward
+    Timestamp(String),
+    /// A row changed when any of the given columns differs
+    Check(Vec<String>),
+}
+
+/// Model configuration declared in the header comments of the model file,
+/// e.g. `-- cadac: materialized=incremental, unique_key=order_id, tags=[finance]`
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct ModelConfig {
+    /// Materialization of the model, falls back to the project default when unset
+    pub materialized: Option<Materialization>,
+    /// Strategy used by incremental models
+    pub incremental_strategy: Option<IncrementalStrategy>,
+    /// Columns identifying a row, used by the merge and delete+insert strategies
+    pub unique_key: Vec<String>,
+    /// Column used to select new rows on incremental runs: only rows with a value
+    /// greater than the current maximum in the target table are processed
+    pub incremental_column: Option<String>,
+    /// Timestamp column used by the timestamp snapshot strategy
+    pub updated_at: Option<String>,
+    /// Columns compared by the check snapshot strategy
+    pub check_cols: Vec<String>,
+    /// Free-form labels used to select groups of models
+    pub tags: Vec<String>,
+    /// How long the model may run before it is cancelled, overriding `--timeout`
+    pub timeout: Option<Duration>,
+}
+
+impl ModelConfig {
+    /// The incremental strategy, defaulting to merge when a unique key is set
+    /// and to append otherwise
+    pub fn resolved_incremental_strategy(&self) -> IncrementalStrategy {
+        match self.incremental_strategy {
+            Some(strategy) => strategy,
+            None if !self.unique_key.is_empty() => IncrementalStrategy::Merge,
+            None => IncrementalStrategy::Append,
+        }
+    }
+
+    /// The snapshot strategy, determined by whether `updated_at` or `check_cols` is set
+    pub fn snapshot_strategy(&self) -> Option<SnapshotStrategy> {
+        match (&self.updated_at, self.check_cols.is_empty()) {
+            (Some(column), true) => Some(SnapshotStrategy::Timestamp(column.clone())),
+            (None, false) => Some(SnapshotStrategy::Check(self.check_cols.clone())),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub struct ModelMetadata {
     pub name: String,
     pub description: Option<String>,
     pub columns: Vec<Column>,
     pub sources: Vec<Source>,
+    pub references: Vec<SourceReference>,
+    /// Names bound by the model's WITH clause, which shadow models and
+    /// external tables of the same name
+    pub cte_names: Vec<String>,
+    pub config: ModelConfig,
 }
 
 pub trait ModelParser {
@@ -91,9 +281,41 @@ impl ModelMetadata {
             description: None,
             columns: vec![],
             sources: vec![],
+            references: vec![],
+            cte_names: vec![],
+            config: ModelConfig::default(),
         }
     }
 
+    /// Model selecting from the given tables, e.g. `raw.orders`, without parsing any SQL
+    #[cfg(test)]
+    pub fn selecting_from(name: &str, source_ids: &[&str]) -> Self {
+        let mut model = Self::new(name.to_string());
+        for source_id in source_ids {
+            let mut parts = source_id.rsplit('.').map(str::to_string);
+            model.sources.push(Source {
+                id: source_id.to_string(),
+                name: parts.next().unwrap_or_default(),
+                description: None,
+                schema: parts.next(),
+                database: parts.next(),
+            });
+            model.references.push(SourceReference {
+                source_id: source_id.to_string(),
+                byte_range: 0..0,
+                line: 1,
+            });
+        }
+        model
+    }
+
+    /// Check if an unqualified name is bound by the model's WITH clause
+    pub fn is_cte(&self, source_id: &str) -> bool {
+        self.cte_names
+            .iter()
+            .any(|name| name.eq_ignore_ascii_case(source_id))
+    }
+
     /// Parse the root node of the tree and extract the select statement
     /// while making sure there is only one statement.
     fn parse_root_node(
@@ -125,15 +347,46 @@ impl ModelMetadata {
             ));
         }
 
-        // Extract model description from the select statement
-        self.extract_model_description(&statement_nodes[0], source_bytes);
+        // Extract model description and configuration from the select statement
+        self.extract_model_description(&statement_nodes[0], source_bytes)?;
 
         // Process the select statement to extract columns and sources
         self.walk_tree(statement_nodes[0], source_bytes);
 
+        // Locate every table reference, including those nested in subqueries
+        self.collect_references(statement_nodes[0], source_bytes);
+
         Ok(())
     }
 
+    // Record the location of every table reference below the node, and the
+    // names bound by every WITH clause, including those in subqueries
+    fn collect_references(&mut self, node: Node, source_bytes: &[u8]) {
+        if node.kind() == NODE_KIND_CTE
+            && let Some(name) = node.named_child(0)
+        {
+            let name = name.utf8_text(source_bytes).unwrap_or("").trim_matches('"');
+            if !self.cte_names.iter().any(|cte_name| cte_name == name) {
+                self.cte_names.push(name.to_string());
+            }
+        }
+
+        if node.kind() == NODE_KIND_TABLE_REFERENCE {
+            let (database_name, schema_name, table_name) = table_reference_names(&node, source_bytes);
+            self.references.push(SourceReference {
+                source_id: qualified_source_name(&database_name, &schema_name, &table_name),
+                byte_range: node.byte_range(),
+                line: node.start_position().row + 1,
+            });
+            return;
+        }
+
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            self.collect_references(child, source_bytes);
+        }
+    }
+
     // Mutable reference to self for updating during parsing
     fn walk_tree(&mut self, n: Node, source_bytes: &[u8]) {
         // Process current node and check if we should continue traversing
@@ -179,9 +432,14 @@ impl ModelMetadata {
         }
     }
 
-    // Extract model description from comments within select_statement
-    fn extract_model_description(&mut self, select_statement_node: &Node, source_bytes: &[u8]) {
+    // Extract model description and config directives from comments within select_statement
+    fn extract_model_description(
+        &mut self,
+        select_statement_node: &Node,
+        source_bytes: &[u8],
+    ) -> Result<(), ModelParseError> {
         let mut comments = Vec::new();
+        let mut config_lines = HashMap::new();
 
         // Look for comment nodes that are direct children of select_statement
         for i in 0..select_statement_node.child_count() {
@@ -189,7 +447,12 @@ impl ModelMetadata {
             if child.kind() == "comment" {
                 // Extract comment_text from the comment node
                 if let Some(comment_text) = self.extract_comment_text(&child, source_bytes) {
-                    comments.push(comment_text);
+                    if let Some(directive) = comment_text.strip_prefix(CONFIG_DIRECTIVE_PREFIX) {
+                        let line = child.start_position().row + 1;
+                        self.apply_config_directive(directive, line, &mut config_lines)?;
+                    } else {
+                        comments.push(comment_text);
+                    }
                 }
             } else if child.kind() == "SELECT" {
                 // Stop when we reach the SELECT keyword
@@ -201,6 +464,114 @@ impl ModelMetadata {
         if !comments.is_empty() {
             self.description = Some(comments.join(" "));
         }
+
+        self.validate_config(&config_lines)
+    }
+
+    // Apply a `key=value, key=value` config directive found on the given line to the model config,
+    // recording the line each key was set on
+    fn apply_config_directive(
+        &mut self,
+        directive: &str,
+        line: usize,
+        config_lines: &mut HashMap<String, usize>,
+    ) -> Result<(), ModelParseError> {
+        for entry in split_directive_entries(directive) {
+            // Tolerate trailing commas
+            if entry.trim().is_empty() {
+                continue;
+            }
+
+            let Some((key, value)) = entry.split_once('=') else {
+                return Err(ModelParseError::MalformedConfigEntry {
+                    entry: entry.trim().to_string(),
+                    line,
+                });
+            };
+
+            let key = key.trim();
+            let value = value.trim();
+            let invalid = |reason: String| ModelParseError::InvalidConfigValue {
+                key: key.to_string(),
+                value: value.to_string(),
+                reason,
+                line,
+            };
+
+            match key {
+                "materialized" => self.config.materialized = Some(value.parse().map_err(invalid)?),
+                "incremental_strategy" => {
+                    self.config.incremental_strategy = Some(value.parse().map_err(invalid)?)
+                }
+                "unique_key" => self.config.unique_key = parse_directive_list(value).map_err(invalid)?,
+                "incremental_column" => {
+                    self.config.incremental_column = Some(parse_directive_name(value).map_err(invalid)?)
+                }
+                "updated_at" => self.config.updated_at = Some(parse_directive_name(value).map_err(invalid)?),
+                "check_cols" => self.config.check_cols = parse_directive_list(value).map_err(invalid)?,
+                "tags" => self.config.tags = parse_directive_list(value).map_err(invalid)?,
+                "timeout" => self.config.timeout = Some(parse_duration(value).map_err(invalid)?),
+                _ => {
+                    return Err(ModelParseError::UnknownConfigKey {
+                        key: key.to_string(),
+                        line,
+                    });
+                }
+            }
+            config_lines.insert(key.to_string(), line);
+        }
+
+        Ok(())
+    }
+
+    // Check that config directives are consistent with each other, given the line each key was set on
+    fn validate_config(&self, config_lines: &HashMap<String, usize>) -> Result<(), ModelParseError> {
+        let conflict = |key: &str, reason: String| ModelParseError::ConflictingConfig {
+            reason,
+            line: config_lines.get(key).copied().unwrap_or(1),
+        };
+
+        if let Some(strategy) = self.config.incremental_strategy
+            && strategy != IncrementalStrategy::Append
+            && self.config.unique_key.is_empty()
+        {
+            return Err(conflict(
+                "incremental_strategy",
+                format!("Incremental strategy '{}' requires a unique_key", strategy),
+            ));
+        }
+
+        if self.config.materialized == Some(Materialization::Incremental)
+            && self.config.resolved_incremental_strategy() == IncrementalStrategy::Append
+            && self.config.incremental_column.is_none()
+        {
+            let key = if self.config.incremental_strategy.is_some() {
+                "incremental_strategy"
+            } else {
+                "materialized"
+            };
+            return Err(conflict(
+                key,
+                "The append strategy requires an incremental_column".to_string(),
+            ));
+        }
+
+        if self.config.materialized == Some(Materialization::Snapshot) {
+            if self.config.unique_key.is_empty() {
+                return Err(conflict(
+                    "materialized",
+                    "Snapshot models require a unique_key".to_string(),
+                ));
+            }
+            if self.config.snapshot_strategy().is_none() {
+                return Err(conflict(
+                    "materialized",
+                    "Snapshot models require exactly one of updated_at or check_cols".to_string(),
+                ));
+            }
+        }
+
+        Ok(())
     }
 
     // Helper function to extract comment_text from a comment node
@@ -242,37 +613,8 @@ impl ModelMetadata {
 
     // Extract source from table reference
     fn extract_source_from_table_reference(&mut self, node: &Node, source_bytes: &[u8]) {
-        let mut table_name = String::new();
-        let mut schema_name = String::new();
-        let mut database_name = String::new();
-
-        // Look for the name components
-        for i in 0..node.child_count() {
-            let child = node.child(i).unwrap();
-            match child.kind() {
-                NODE_KIND_TABLE_NAME => {
-                    table_name = child.utf8_text(source_bytes).unwrap_or("").to_string();
-                }
-                NODE_KIND_SCHEMA_NAME => {
-                    schema_name = child.utf8_text(source_bytes).unwrap_or("").to_string();
-                }
-                NODE_KIND_DATABASE_NAME => {
-                    database_name = child.utf8_text(source_bytes).unwrap_or("").to_string();
-                }
-                _ => {}
-            }
-        }
-
-        // table name should never be empty
-        assert!(!table_name.is_empty());
-
-        let source_name = if !database_name.is_empty() && !schema_name.is_empty() {
-            format!("{}.{}.{}", database_name, schema_name, table_name)
-        } else if !schema_name.is_empty() {
-            format!("{}.{}", schema_name, table_name)
-        } else {
-            table_name.clone()
-        };
+        let (database_name, schema_name, table_name) = table_reference_names(node, source_bytes);
+        let source_name = qualified_source_name(&database_name, &schema_name, &table_name);
 
         // Check if this source already exists
         let mut found = false;
@@ -392,3 +734,117 @@ impl ModelMetadata {
         }
     }
 }
+
+/// Get the database, schema and table names of a table reference, empty when not given
+fn table_reference_names(node: &Node, source_bytes: &[u8]) -> (String, String, String) {
+    let mut table_name = String::new();
+    let mut schema_name = String::new();
+    let mut database_name = String::new();
+
+    // Look for the name components
+    for i in 0..node.child_count() {
+        let child = node.child(i).unwrap();
+        match child.kind() {
+            NODE_KIND_TABLE_NAME => {
+                table_name = child.utf8_text(source_bytes).unwrap_or("").to_string();
+            }
+            NODE_KIND_SCHEMA_NAME => {
+                schema_name = child.utf8_text(source_bytes).unwrap_or("").to_string();
+            }
+            NODE_KIND_DATABASE_NAME => {
+                database_name = child.utf8_text(source_bytes).unwrap_or("").to_string();
+            }
+            _ => {}
+        }
+    }
+
+    // table name should never be empty
+    assert!(!table_name.is_empty());
+
+    (database_name, schema_name, table_name)
+}
+
+/// Join the non-empty parts of a table reference into its source id
+fn qualified_source_name(database_name: &str, schema_name: &str, table_name: &str) -> String {
+    if !database_name.is_empty() && !schema_name.is_empty() {
+        format!("{}.{}.{}", database_name, schema_name, table_name)
+    } else if !schema_name.is_empty() {
+        format!("{}.{}", schema_name, table_name)
+    } else {
+        table_name.to_string()
+    }
+}
+
+/// Split a config directive on commas that are not inside a `[...]` list
+fn split_directive_entries(directive: &str) -> Vec<&str> {
+    let mut entries = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+
+    for (i, c) in directive.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            ',' if depth == 0 => {
+                entries.push(&directive[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    entries.push(&directive[start..]);
+
+    entries
+}
+
+/// Parse a directive value naming a single column or label
+fn parse_directive_name(value: &str) -> Result<String, String> {
+    if value.is_empty() {
+        return Err("expected a value".to_string());
+    }
+    if value.contains(|c: char| c.is_whitespace() || "[],".contains(c)) {
+        return Err("expected a single name".to_string());
+    }
+
+    Ok(value.to_string())
+}
+
+/// Parse a directive value that is either a single item or a `[a, b]` list
+fn parse_directive_list(value: &str) -> Result<Vec<String>, String> {
+    let items = match value.strip_prefix('[') {
+        Some(list) => list
+            .strip_suffix(']')
+            .ok_or_else(|| "list is missing its closing ']'".to_string())?,
+        None => value,
+    };
+
+    let names = items
+        .split(',')
+        .map(|item| parse_directive_name(item.trim()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(names)
+}
+
+/// Parse a duration given in seconds, optionally with an `s`, `m` or `h` suffix
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let (amount, unit_seconds) = match value.trim() {
+        value if value.ends_with('h') => (&value[..value.len() - 1], 3600),
+        value if value.ends_with('m') => (&value[..value.len() - 1], 60),
+        value if value.ends_with('s') => (&value[..value.len() - 1], 1),
+        value => (value, 1),
+    };
+    let amount: u64 = amount
+        .trim()
+        .parse()
+        .map_err(|_| "expected a duration such as 30, 90s, 5m or 1h".to_string())?;
+    if amount == 0 {
+        return Err("duration must be greater than zero".to_string());
+    }
+
+    let seconds = amount
+        .checked_mul(unit_seconds)
+        .ok_or_else(|| "duration is too long".to_string())?;
+
+    Ok(Duration::from_secs(seconds))
+}
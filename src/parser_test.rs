@@ -1,5 +1,9 @@
-use crate::parser::{ModelMetadata, ModelParser};
+use crate::parser::{
+    IncrementalStrategy, Materialization, ModelMetadata, ModelParseError, ModelParser,
+    SnapshotStrategy, parse_duration,
+};
 use color_eyre::Result;
+use std::time::Duration;
 
 #[test]
 fn test_parse_simple_select() -> Result<()> {
@@ -100,3 +104,205 @@ fn test_parse_select_with_comments() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_parse_config_directive() -> Result<()> {
+    let sql = "-- Staging users
+    -- cadac: materialized=view, tags=[finance, daily], timeout=5m
+    SELECT a FROM source_table";
+
+    let mut model = ModelMetadata::new("test_model".to_string());
+    let result = model.parse_model(sql)?;
+
+    assert_eq!(result.config.materialized, Some(Materialization::View));
+    assert_eq!(result.config.tags, vec!["finance", "daily"]);
+    assert_eq!(result.config.timeout, Some(Duration::from_secs(300)));
+
+    // Directives are not part of the description
+    assert_eq!(result.description.as_deref(), Some("Staging users"));
+
+    Ok(())
+}
+
+#[test]
+fn test_parse_invalid_materialization() {
+    let sql = "-- Model description
+    -- cadac: materialized=snapshotted
+    SELECT a FROM source_table";
+
+    let mut model = ModelMetadata::new("test_model".to_string());
+    match model.parse_model(sql) {
+        Err(ModelParseError::InvalidConfigValue {
+            key, value, line, ..
+        }) => {
+            assert_eq!(key, "materialized");
+            assert_eq!(value, "snapshotted");
+            assert_eq!(line, 2);
+        }
+        other => panic!("Expected an invalid config value error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_parse_config_directive_errors() {
+    let sql = "-- cadac: materialized=view, unique_keys=id
+    SELECT a FROM source_table";
+    let mut model = ModelMetadata::new("test_model".to_string());
+    assert_eq!(
+        model.parse_model(sql).err(),
+        Some(ModelParseError::UnknownConfigKey {
+            key: "unique_keys".to_string(),
+            line: 1
+        })
+    );
+
+    let sql = "-- cadac: materialized view
+    SELECT a FROM source_table";
+    let mut model = ModelMetadata::new("test_model".to_string());
+    assert_eq!(
+        model.parse_model(sql).err(),
+        Some(ModelParseError::MalformedConfigEntry {
+            entry: "materialized view".to_string(),
+            line: 1
+        })
+    );
+
+    let sql = "-- cadac: tags=[finance, daily
+    SELECT a FROM source_table";
+    let mut model = ModelMetadata::new("test_model".to_string());
+    assert!(matches!(
+        model.parse_model(sql),
+        Err(ModelParseError::InvalidConfigValue { .. })
+    ));
+}
+
+#[test]
+fn test_parse_incremental_config() -> Result<()> {
+    let sql = "-- cadac: materialized=incremental, incremental_strategy=delete+insert
+    -- cadac: unique_key=order_id, incremental_column=updated_at
+    SELECT order_id, updated_at FROM source_table";
+
+    let mut model = ModelMetadata::new("test_model".to_string());
+    let result = model.parse_model(sql)?;
+
+    assert_eq!(result.config.materialized, Some(Materialization::Incremental));
+    assert_eq!(
+        result.config.resolved_incremental_strategy(),
+        IncrementalStrategy::DeleteInsert
+    );
+    assert_eq!(result.config.unique_key, vec!["order_id"]);
+    assert_eq!(result.config.incremental_column.as_deref(), Some("updated_at"));
+
+    Ok(())
+}
+
+#[test]
+fn test_parse_merge_without_unique_key() {
+    let sql = "-- Orders
+    -- cadac: materialized=incremental, incremental_column=updated_at
+    -- cadac: incremental_strategy=merge
+    SELECT a FROM source_table";
+
+    let mut model = ModelMetadata::new("test_model".to_string());
+    assert_eq!(
+        model.parse_model(sql).err(),
+        Some(ModelParseError::ConflictingConfig {
+            reason: "Incremental strategy 'merge' requires a unique_key".to_string(),
+            line: 3
+        })
+    );
+}
+
+#[test]
+fn test_parse_append_without_incremental_column() {
+    // Appending without a watermark would insert every row again on each run
+    let sql = "-- cadac: materialized=incremental
+    SELECT a FROM source_table";
+    let mut model = ModelMetadata::new("test_model".to_string());
+    assert!(matches!(
+        model.parse_model(sql),
+        Err(ModelParseError::ConflictingConfig { line: 1, .. })
+    ));
+
+    let sql = "-- cadac: materialized=incremental, incremental_column=loaded_at
+    SELECT a, loaded_at FROM source_table";
+    let mut model = ModelMetadata::new("test_model".to_string());
+    model.parse_model(sql).unwrap();
+    assert_eq!(model.config.resolved_incremental_strategy(), IncrementalStrategy::Append);
+}
+
+#[test]
+fn test_parse_snapshot_config() {
+    let sql = "-- cadac: materialized=snapshot, unique_key=user_id, check_cols=[email, plan]
+    SELECT user_id, email, plan FROM source_users";
+
+    let mut model = ModelMetadata::new("test_model".to_string());
+    model.parse_model(sql).unwrap();
+
+    assert_eq!(model.config.materialized, Some(Materialization::Snapshot));
+    assert_eq!(
+        model.config.snapshot_strategy(),
+        Some(SnapshotStrategy::Check(vec![
+            "email".to_string(),
+            "plan".to_string()
+        ]))
+    );
+
+    // A snapshot needs exactly one way of detecting changes
+    let sql = "-- User history
+    -- cadac: materialized=snapshot, unique_key=user_id
+    SELECT user_id, email FROM source_users";
+    let mut model = ModelMetadata::new("test_model".to_string());
+    match model.parse_model(sql) {
+        Err(ModelParseError::ConflictingConfig { reason, line }) => {
+            assert_eq!(reason, "Snapshot models require exactly one of updated_at or check_cols");
+            assert_eq!(line, 2);
+        }
+        other => panic!("Expected a conflicting config error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_parse_duration() {
+    assert_eq!(parse_duration("30"), Ok(Duration::from_secs(30)));
+    assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+    assert_eq!(parse_duration("5m"), Ok(Duration::from_secs(300)));
+    assert_eq!(parse_duration("1h"), Ok(Duration::from_secs(3600)));
+    assert!(parse_duration("0").is_err());
+    assert!(parse_duration("soon").is_err());
+    assert_eq!(
+        parse_duration("18446744073709551615h"),
+        Err("duration is too long".to_string())
+    );
+}
+
+#[test]
+fn test_parse_cte_names() -> Result<()> {
+    let sql = "-- Active users
+    WITH active AS (
+        SELECT id FROM users WHERE active
+    )
+    SELECT a.id
+    FROM active a
+    JOIN (
+        WITH recent AS (SELECT user_id FROM events)
+        SELECT user_id FROM recent
+    ) r ON a.id = r.user_id";
+
+    let mut model = ModelMetadata::new("test_model".to_string());
+    let result = model.parse_model(sql)?;
+
+    // Names bound in subqueries count too, the tables they select from are still references
+    assert_eq!(result.cte_names, vec!["active", "recent"]);
+    assert!(result.is_cte("Active"));
+    assert!(!result.is_cte("users"));
+    let references: Vec<&str> = result
+        .references
+        .iter()
+        .map(|reference| reference.source_id.as_str())
+        .collect();
+    assert!(references.contains(&"users"));
+    assert!(references.contains(&"events"));
+
+    Ok(())
+}
@@ -0,0 +1,323 @@
+use color_eyre::Result;
+use color_eyre::eyre::{Context, eyre};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::ProjectConfig;
+use crate::execution::SqlDialect;
+
+/// Name of the profiles file, looked up in the project root and then in `~/.cadac/`
+pub const PROFILES_FILE: &str = "profiles.toml";
+
+/// Connection profiles loaded from `profiles.toml`
+///
+/// ```toml
+/// default_target = "dev"
+///
+/// [targets.dev]
+/// type = "postgres"
+/// host = "localhost"
+/// database = "warehouse"
+/// user = "jan"
+/// password = "${CADAC_DEV_PASSWORD}"
+/// schema_prefix = "dev_jan"
+/// threads = 4
+/// retries = 2
+///
+/// [targets.prod]
+/// type = "postgres"
+/// host = "warehouse.example.com"
+/// database = "warehouse"
+/// user = "cadac"
+/// sslmode = "verify-full"
+/// sslrootcert = "${HOME}/.cadac/ca.pem"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profiles {
+    /// Target used when `--target` is not given
+    pub default_target: Option<String>,
+    /// Named targets such as dev, staging and prod
+    pub targets: HashMap<String, TargetProfile>,
+}
+
+/// A database to run models against. String fields may reference environment
+/// variables as `${NAME}` so secrets stay out of the profiles file.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TargetProfile {
+    /// Name of the target, filled in from its key in the profiles file
+    #[serde(skip)]
+    pub name: String,
+    /// Adapter used to connect, e.g. `type = "postgres"`
+    #[serde(rename = "type")]
+    pub dialect: SqlDialect,
+    pub host: String,
+    pub port: Option<u16>,
+    pub database: String,
+    pub user: String,
+    pub password: Option<String>,
+    /// Prefix added to every schema models are built in, e.g. for per-developer schemas
+    pub schema_prefix: Option<String>,
+    /// Number of models run concurrently
+    pub threads: Option<usize>,
+    /// Times a model failing with a transient error, such as a lost connection, is retried
+    pub retries: Option<u32>,
+    /// TLS mode: disable, prefer, require, verify-ca or verify-full
+    pub sslmode: Option<String>,
+    /// PEM bundle of CA certificates to verify the server with
+    pub sslrootcert: Option<String>,
+    /// PEM client certificate and its PKCS#8 key, for certificate authentication
+    pub sslcert: Option<String>,
+    pub sslkey: Option<String>,
+    /// Full connection string, used as-is instead of the fields above
+    #[serde(skip)]
+    pub connection_string: Option<String>,
+}
+
+impl Profiles {
+    /// Load the profiles file from the project directory or `~/.cadac/`, if either exists
+    pub fn load(project_dir: &Path) -> Result<Option<Self>> {
+        let home_profiles = std::env::var_os("HOME")
+            .map(|home| PathBuf::from(home).join(".cadac").join(PROFILES_FILE));
+        let Some(profiles_path) = std::iter::once(project_dir.join(PROFILES_FILE))
+            .chain(home_profiles)
+            .find(|path| path.exists())
+        else {
+            return Ok(None);
+        };
+
+        let content = fs::read_to_string(&profiles_path)
+            .wrap_err_with(|| format!("Failed to read profiles: {:?}", profiles_path))?;
+        let profiles = toml::from_str(&content)
+            .wrap_err_with(|| format!("Failed to parse profiles: {:?}", profiles_path))?;
+
+        Ok(Some(profiles))
+    }
+
+    /// Get a target by name, or the default target. Only the fields every
+    /// command uses are interpolated here; credentials and certificate paths
+    /// are left for when a connection is opened, so commands that never
+    /// connect don't need them set.
+    pub fn target(
+        &self,
+        name: Option<&str>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<TargetProfile> {
+        let name = name
+            .or(self.default_target.as_deref())
+            .ok_or_else(|| eyre!("No target given. Pass --target or set default_target in {}", PROFILES_FILE))?;
+        let mut target = self.targets.get(name).cloned().ok_or_else(|| {
+            let mut available: Vec<&String> = self.targets.keys().collect();
+            available.sort();
+            eyre!("Unknown target '{}'. Available targets: {:?}", name, available)
+        })?;
+        target.name = name.to_string();
+        if target.threads == Some(0) {
+            return Err(eyre!("Invalid target '{}': threads must be at least 1", name));
+        }
+
+        let interpolate = |value: &str| {
+            interpolate_env(value, &env).wrap_err_with(|| format!("Invalid target '{}'", name))
+        };
+        target.database = interpolate(&target.database)?;
+        if let Some(schema_prefix) = &target.schema_prefix {
+            target.schema_prefix = Some(interpolate(schema_prefix)?);
+        }
+
+        Ok(target)
+    }
+}
+
+impl TargetProfile {
+    /// Create an unnamed target from a full connection string, detecting the
+    /// adapter from its scheme. libpq's key/value form is PostgreSQL's own.
+    pub fn from_connection_string(connection_string: &str) -> Result<Self> {
+        let is_key_value = !connection_string.contains("://") && connection_string.contains('=');
+        let dialect = if connection_string.starts_with("postgresql://")
+            || connection_string.starts_with("postgres://")
+            || is_key_value
+        {
+            SqlDialect::Postgres
+        } else {
+            return Err(eyre!(
+                "Cannot determine database dialect from connection string. Supported forms:\n\
+                - PostgreSQL: postgresql://, postgres:// or key/value pairs such as host=localhost dbname=warehouse"
+            ));
+        };
+
+        Ok(Self {
+            name: "connection".to_string(),
+            dialect,
+            host: String::new(),
+            port: None,
+            database: String::new(),
+            user: String::new(),
+            password: None,
+            schema_prefix: None,
+            threads: None,
+            retries: None,
+            sslmode: None,
+            sslrootcert: None,
+            sslcert: None,
+            sslkey: None,
+            connection_string: Some(connection_string.to_string()),
+        })
+    }
+
+    /// Database that relations are qualified with. PostgreSQL connections are
+    /// scoped to a single database, so its relations are only schema-qualified.
+    pub fn relation_database(&self) -> Option<String> {
+        match self.dialect {
+            SqlDialect::Postgres => None,
+            _ => Some(self.database.clone()).filter(|database| !database.is_empty()),
+        }
+    }
+}
+
+/// Resolve the named or default target from the profiles file, falling back to
+/// the connection in cadac.toml. None when no target is configured at all.
+/// Nothing is connected to, so this also serves commands that only need the
+/// target's dialect and schema prefix.
+pub fn configured_target(
+    project_dir: &Path,
+    config: &ProjectConfig,
+    cli_target: Option<&str>,
+) -> Result<Option<TargetProfile>> {
+    match Profiles::load(project_dir)? {
+        Some(profiles) if cli_target.is_some() || profiles.default_target.is_some() => profiles
+            .target(cli_target, |name| std::env::var(name).ok())
+            .map(Some),
+        _ if cli_target.is_some() => Err(eyre!(
+            "Cannot use --target without a {} file",
+            PROFILES_FILE
+        )),
+        _ => config
+            .project
+            .connection
+            .as_deref()
+            .map(TargetProfile::from_connection_string)
+            .transpose(),
+    }
+}
+
+/// Replace `${NAME}` references with values from the environment
+pub fn interpolate_env(value: &str, env: impl Fn(&str) -> Option<String>) -> Result<String> {
+    let mut interpolated = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(start) = rest.find("${") {
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| eyre!("Unterminated environment variable reference in '{}'", value))?;
+        let name = &rest[start + 2..start + end];
+        let resolved = env(name).ok_or_else(|| eyre!("Environment variable '{}' is not set", name))?;
+
+        interpolated.push_str(&rest[..start]);
+        interpolated.push_str(&resolved);
+        rest = &rest[start + end + 1..];
+    }
+    interpolated.push_str(rest);
+
+    Ok(interpolated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROFILES: &str = r#"
+default_target = "dev"
+
+[targets.dev]
+type = "postgres"
+host = "localhost"
+database = "warehouse"
+user = "${USER_NAME}"
+password = "${DEV_PASSWORD}"
+schema_prefix = "dev_${USER_NAME}"
+threads = 4
+retries = 2
+
+[targets.prod]
+type = "postgres"
+host = "db.internal"
+port = 6432
+database = "warehouse"
+user = "cadac"
+sslmode = "verify-full"
+sslrootcert = "${CERT_DIR}/ca.pem"
+"#;
+
+    fn env(name: &str) -> Option<String> {
+        match name {
+            "USER_NAME" => Some("jan".to_string()),
+            "DEV_PASSWORD" => Some("p@ss word".to_string()),
+            "CERT_DIR" => Some("/etc/cadac".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_resolve_target() -> Result<()> {
+        let profiles: Profiles = toml::from_str(PROFILES)?;
+
+        let dev = profiles.target(None, env)?;
+        assert_eq!(dev.name, "dev");
+        assert_eq!(dev.dialect, SqlDialect::Postgres);
+        assert_eq!(dev.schema_prefix.as_deref(), Some("dev_jan"));
+        assert_eq!(dev.threads, Some(4));
+        assert_eq!(dev.retries, Some(2));
+
+        assert!(profiles.target(Some("staging"), env).is_err());
+
+        let profiles: Profiles = toml::from_str(
+            "[targets.dev]\ntype = \"postgres\"\nhost = \"localhost\"\ndatabase = \"warehouse\"\nuser = \"jan\"\nthreads = 0",
+        )?;
+        assert!(profiles.target(Some("dev"), env).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_environment_variable() -> Result<()> {
+        let profiles: Profiles = toml::from_str(PROFILES)?;
+        let result = profiles.target(Some("dev"), |_| None);
+
+        let message = format!("{:?}", result.unwrap_err());
+        assert!(message.contains("USER_NAME"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_target_without_connection_secrets() -> Result<()> {
+        let profiles: Profiles = toml::from_str(PROFILES)?;
+        let env = |name: &str| (name == "USER_NAME").then(|| "jan".to_string());
+
+        let dev = profiles.target(Some("dev"), env)?;
+        assert_eq!(dev.password.as_deref(), Some("${DEV_PASSWORD}"));
+        assert_eq!(dev.schema_prefix.as_deref(), Some("dev_jan"));
+
+        let prod = profiles.target(Some("prod"), env)?;
+        assert_eq!(prod.sslrootcert.as_deref(), Some("${CERT_DIR}/ca.pem"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_target_from_connection_string() -> Result<()> {
+        let target = TargetProfile::from_connection_string("postgres://localhost/db")?;
+        assert_eq!(target.dialect, SqlDialect::Postgres);
+        assert_eq!(target.connection_string.as_deref(), Some("postgres://localhost/db"));
+
+        let target = TargetProfile::from_connection_string("host=localhost dbname=db sslmode=require")?;
+        assert_eq!(target.dialect, SqlDialect::Postgres);
+
+        assert!(TargetProfile::from_connection_string("mysql://localhost/db").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,346 @@
+use color_eyre::Result;
+use color_eyre::eyre::{Context, eyre};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::dependency_graph::DependencyGraph;
+use crate::execution::scheduler::ModelOutcome;
+use crate::execution::{
+    ExecutionResult, ExecutionStatus, RunOptions, SqlDialect, serialize_seconds,
+    serialize_timestamp,
+};
+use crate::profiles::TargetProfile;
+
+/// File the results of the last `command` invocation are written to, relative
+/// to the project root. Each command keeps its own, so a snapshot doesn't
+/// replace the results a `cadac run --retry-failed` resumes from.
+pub fn results_file(command: &str) -> PathBuf {
+    PathBuf::from(format!("target/{}_results.json", command))
+}
+
+/// Name of the command the options were given to
+pub fn command(options: &RunOptions) -> &'static str {
+    if options.snapshot { "snapshot" } else { "run" }
+}
+
+/// Machine-readable record of a `cadac run` or `cadac snapshot` invocation
+#[derive(Debug, Serialize)]
+pub struct RunResults {
+    pub metadata: RunMetadata,
+    pub args: RunArgs,
+    pub target: RunTarget,
+    #[serde(serialize_with = "serialize_seconds")]
+    pub elapsed_time: Duration,
+    /// Every selected model, in execution order
+    pub results: Vec<ModelRunResult>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RunMetadata {
+    pub cadac_version: &'static str,
+    pub command: &'static str,
+    #[serde(serialize_with = "serialize_timestamp")]
+    pub generated_at: SystemTime,
+}
+
+/// The arguments that selected and configured the run
+#[derive(Debug, Serialize)]
+pub struct RunArgs {
+    pub select: Vec<String>,
+    pub exclude: Vec<String>,
+    pub full_refresh: bool,
+    pub retry_failed: bool,
+    pub fail_fast: bool,
+    pub threads: usize,
+    pub retries: u32,
+    /// Default model timeout in seconds
+    pub timeout: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RunTarget {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub dialect: SqlDialect,
+}
+
+/// Status of a model in the run, including models that never produced an
+/// execution result
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModelRunStatus {
+    Success,
+    Failed,
+    Skipped,
+    /// Execution stopped with an error before the database reported a result
+    Error,
+    /// Not started because the run stopped after an earlier failure
+    NotRun,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ModelRunResult {
+    pub model: String,
+    pub status: ModelRunStatus,
+    /// The execution result, when the model produced one
+    pub result: Option<ExecutionResult>,
+    /// The error, when execution stopped before producing a result
+    pub error: Option<String>,
+}
+
+impl From<ModelOutcome> for ModelRunResult {
+    fn from(outcome: ModelOutcome) -> Self {
+        let (status, result, error) = match outcome.result {
+            Some(Ok(result)) => {
+                let status = match result.status {
+                    ExecutionStatus::Success => ModelRunStatus::Success,
+                    ExecutionStatus::Failed => ModelRunStatus::Failed,
+                    ExecutionStatus::Skipped => ModelRunStatus::Skipped,
+                };
+                (status, Some(result), None)
+            }
+            Some(Err(e)) => (ModelRunStatus::Error, None, Some(e.to_string())),
+            None => (ModelRunStatus::NotRun, None, None),
+        };
+
+        Self {
+            model: outcome.model,
+            status,
+            result,
+            error,
+        }
+    }
+}
+
+impl RunResults {
+    pub fn new(
+        options: &RunOptions,
+        target: &TargetProfile,
+        elapsed_time: Duration,
+        outcomes: Vec<ModelOutcome>,
+    ) -> Self {
+        Self {
+            metadata: RunMetadata {
+                cadac_version: env!("CARGO_PKG_VERSION"),
+                command: command(options),
+                generated_at: SystemTime::now(),
+            },
+            args: RunArgs {
+                select: options.select.clone(),
+                exclude: options.exclude.clone(),
+                full_refresh: options.full_refresh,
+                retry_failed: options.retry_failed,
+                fail_fast: options.fail_fast,
+                threads: options.threads,
+                retries: options.retry.retries,
+                timeout: options.timeout.map(|timeout| timeout.as_secs_f64()),
+            },
+            target: RunTarget {
+                name: target.name.clone(),
+                dialect: target.dialect.clone(),
+            },
+            elapsed_time,
+            results: outcomes.into_iter().map(ModelRunResult::from).collect(),
+        }
+    }
+
+    /// Write the results as JSON, replacing the results of any previous run
+    pub fn write(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .wrap_err_with(|| format!("Failed to create directory: {:?}", parent))?;
+        }
+        let json =
+            serde_json::to_string_pretty(self).wrap_err("Failed to serialize run results")?;
+        fs::write(path, json).wrap_err_with(|| format!("Failed to write run results: {:?}", path))
+    }
+}
+
+/// The outcome of each model in the previous run, read back from its run
+/// results so a failed run can be resumed
+#[derive(Debug, Deserialize)]
+pub struct PreviousRun {
+    metadata: PreviousRunMetadata,
+    results: Vec<PreviousModelResult>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PreviousRunMetadata {
+    command: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct PreviousModelResult {
+    model: String,
+    status: ModelRunStatus,
+}
+
+impl PreviousRun {
+    /// Load the results of the previous `command` invocation
+    pub fn load(path: &Path, command: &str) -> Result<Self> {
+        if !path.exists() {
+            return Err(eyre!(
+                "No previous run to retry: {} does not exist",
+                path.display()
+            ));
+        }
+        let content = fs::read_to_string(path)
+            .wrap_err_with(|| format!("Failed to read run results: {:?}", path))?;
+        let previous: Self = serde_json::from_str(&content)
+            .wrap_err_with(|| format!("Failed to parse run results: {:?}", path))?;
+
+        if previous.metadata.command != command {
+            return Err(eyre!(
+                "The previous invocation was `cadac {}`, not `cadac {}`, so there is nothing to retry",
+                previous.metadata.command,
+                command
+            ));
+        }
+        Ok(previous)
+    }
+
+    /// Models that didn't succeed in the previous run, plus everything
+    /// downstream of them, since their inputs may have changed once rebuilt
+    pub fn models_to_retry(&self, graph: &DependencyGraph) -> Vec<String> {
+        let mut models: Vec<String> = Vec::new();
+        for result in &self.results {
+            if result.status != ModelRunStatus::Success {
+                models.push(result.model.clone());
+                models.extend(graph.get_downstream(&result.model, None));
+            }
+        }
+        models.sort();
+        models.dedup();
+        models
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execution::{ErrorDetails, ExecutionErrorKind};
+
+    #[test]
+    fn test_run_results_json() -> Result<()> {
+        let mut error = ErrorDetails::new(
+            ExecutionErrorKind::MissingRelation,
+            "relation \"raw_users\" does not exist",
+        );
+        error.code = Some("42P01".to_string());
+        let outcomes = vec![
+            ModelOutcome {
+                model: "staging.users".to_string(),
+                result: Some(Ok(ExecutionResult::new(ExecutionStatus::Success)
+                    .with_rows_affected(42)
+                    .with_execution_time(Duration::from_millis(1500))
+                    .with_started_at(SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+                    .with_query_hash("abc123".to_string()))),
+            },
+            ModelOutcome {
+                model: "gold.orders".to_string(),
+                result: Some(Ok(
+                    ExecutionResult::new(ExecutionStatus::Failed).with_error(error)
+                )),
+            },
+            ModelOutcome {
+                model: "gold.revenue".to_string(),
+                result: Some(Err(eyre!("Unknown model: gold.revenue"))),
+            },
+            ModelOutcome {
+                model: "gold.customers".to_string(),
+                result: None,
+            },
+        ];
+        let options = RunOptions {
+            select: vec!["+gold.orders".to_string()],
+            threads: 4,
+            timeout: Some(Duration::from_secs(300)),
+            ..RunOptions::default()
+        };
+        let target = TargetProfile::from_connection_string("postgres://localhost/db")?;
+
+        let run_results = RunResults::new(&options, &target, Duration::from_secs(2), outcomes);
+        let json: serde_json::Value = serde_json::to_value(&run_results)?;
+
+        assert_eq!(json["metadata"]["command"], "run");
+        assert_eq!(json["args"]["select"], serde_json::json!(["+gold.orders"]));
+        assert_eq!(json["args"]["threads"], 4);
+        assert_eq!(json["args"]["timeout"], 300.0);
+        assert_eq!(json["target"]["type"], "postgres");
+        assert_eq!(json["elapsed_time"], 2.0);
+
+        let results = json["results"].as_array().unwrap();
+        assert_eq!(results[0]["status"], "success");
+        assert_eq!(results[0]["result"]["rows_affected"], 42);
+        assert_eq!(results[0]["result"]["execution_time"], 1.5);
+        assert_eq!(results[0]["result"]["started_at"], "2023-11-14T22:13:20Z");
+        assert_eq!(results[0]["result"]["query_hash"], "abc123");
+        assert_eq!(results[1]["status"], "failed");
+        assert_eq!(results[1]["result"]["error"]["kind"], "MISSING_RELATION");
+        assert_eq!(results[1]["result"]["error"]["code"], "42P01");
+        assert_eq!(results[2]["status"], "error");
+        assert_eq!(results[2]["error"], "Unknown model: gold.revenue");
+        assert_eq!(results[3]["status"], "not_run");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_results_file_per_command() {
+        let snapshot = RunOptions {
+            snapshot: true,
+            ..RunOptions::default()
+        };
+
+        assert_eq!(command(&RunOptions::default()), "run");
+        assert_eq!(command(&snapshot), "snapshot");
+        assert_eq!(
+            results_file("run"),
+            PathBuf::from("target/run_results.json")
+        );
+        assert_eq!(
+            results_file("snapshot"),
+            PathBuf::from("target/snapshot_results.json")
+        );
+    }
+
+    #[test]
+    fn test_models_to_retry() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let path = temp_dir.path().join("run_results.json");
+
+        assert!(PreviousRun::load(&path, "run").is_err());
+
+        // A succeeded, B failed, C was skipped because of B and D never started
+        let outcomes = ["A", "B", "C", "D"]
+            .into_iter()
+            .zip([
+                Some(ExecutionStatus::Success),
+                Some(ExecutionStatus::Failed),
+                Some(ExecutionStatus::Skipped),
+                None,
+            ])
+            .map(|(model, status)| ModelOutcome {
+                model: model.to_string(),
+                result: status.map(|status| Ok(ExecutionResult::new(status))),
+            })
+            .collect();
+        let target = TargetProfile::from_connection_string("postgres://localhost/db")?;
+        RunResults::new(&RunOptions::default(), &target, Duration::ZERO, outcomes).write(&path)?;
+
+        // E is built on B and succeeded in an earlier run, but must be rebuilt too
+        let mut graph = DependencyGraph::new();
+        graph.add_dependency("B", "A")?;
+        graph.add_dependency("C", "B")?;
+        graph.add_dependency("E", "B")?;
+
+        let previous = PreviousRun::load(&path, "run")?;
+        assert_eq!(previous.models_to_retry(&graph), vec!["B", "C", "D", "E"]);
+
+        assert!(PreviousRun::load(&path, "snapshot").is_err());
+
+        Ok(())
+    }
+}
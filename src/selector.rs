@@ -0,0 +1,395 @@
+use color_eyre::Result;
+use color_eyre::eyre::eyre;
+use std::collections::BTreeSet;
+use std::path::{Component, Path, PathBuf};
+
+use crate::discovery::ModelCatalog;
+
+/// How far a selector extends through the dependency graph
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Depth {
+    /// Follow dependencies up to this many edges away, e.g. `bronze.users+2`
+    Limited(usize),
+    /// Follow dependencies all the way, e.g. `+gold.orders`
+    Unlimited,
+}
+
+impl Depth {
+    fn limit(self) -> Option<usize> {
+        match self {
+            Depth::Limited(depth) => Some(depth),
+            Depth::Unlimited => None,
+        }
+    }
+}
+
+/// What a selector matches models by
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SelectionMethod {
+    /// Qualified model name, e.g. `gold.orders`
+    Model(String),
+    /// Schema folder, e.g. `schema:bronze`
+    Schema(String),
+    /// Tag from the model or its schema folder, e.g. `tag:finance`
+    Tag(String),
+    /// Model file or directory, e.g. `path:models/gold`
+    Path(PathBuf),
+}
+
+/// A single selector such as `+gold.orders` or `schema:bronze+1`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Criterion {
+    pub method: SelectionMethod,
+    /// Also select ancestors of the matched models
+    pub upstream: Option<Depth>,
+    /// Also select descendants of the matched models
+    pub downstream: Option<Depth>,
+}
+
+/// A selection of models: space-separated terms are unioned and the
+/// comma-separated criteria within a term are intersected, so
+/// `tag:finance,schema:gold +gold.orders` selects the finance models in gold
+/// plus gold.orders and its ancestors
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selector {
+    terms: Vec<Vec<Criterion>>,
+}
+
+impl Selector {
+    pub fn parse(selector: &str) -> Result<Self> {
+        let terms = selector
+            .split_whitespace()
+            .map(|term| {
+                term.split(',')
+                    .map(Criterion::parse)
+                    .collect::<Result<Vec<_>>>()
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { terms })
+    }
+
+    /// Models in the catalog that the selector selects
+    pub fn select(&self, catalog: &ModelCatalog) -> Result<BTreeSet<String>> {
+        let mut selected = BTreeSet::new();
+        for term in &self.terms {
+            let mut matched: Option<BTreeSet<String>> = None;
+            for criterion in term {
+                let models = criterion.select(catalog)?;
+                matched = Some(match matched {
+                    Some(matched) => matched.intersection(&models).cloned().collect(),
+                    None => models,
+                });
+            }
+            selected.extend(matched.unwrap_or_default());
+        }
+        Ok(selected)
+    }
+}
+
+impl Criterion {
+    pub fn parse(text: &str) -> Result<Self> {
+        let invalid = |reason: &str| eyre!("Invalid selector '{}': {}", text, reason);
+
+        // A leading `+` or `N+` selects ancestors
+        let mut rest = text;
+        let mut upstream = None;
+        let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        if let Some(after) = rest[digits..].strip_prefix('+') {
+            upstream = Some(parse_depth(text, &rest[..digits])?);
+            rest = after;
+        }
+
+        // A trailing `+` or `+N` selects descendants
+        let mut downstream = None;
+        if let Some((before, depth)) = rest.rsplit_once('+')
+            && depth.chars().all(|c| c.is_ascii_digit())
+        {
+            downstream = Some(parse_depth(text, depth)?);
+            rest = before;
+        }
+
+        let method = match rest.split_once(':') {
+            Some(("schema", value)) => SelectionMethod::Schema(value.to_string()),
+            Some(("tag", value)) => SelectionMethod::Tag(value.to_string()),
+            Some(("path", value)) => SelectionMethod::Path(PathBuf::from(value)),
+            Some(("model", value)) => SelectionMethod::Model(value.to_string()),
+            Some((method, _)) => {
+                return Err(invalid(&format!(
+                    "unknown method '{}', expected schema, tag, path or model",
+                    method
+                )));
+            }
+            None => SelectionMethod::Model(rest.to_string()),
+        };
+        let value_is_empty = match &method {
+            SelectionMethod::Model(value)
+            | SelectionMethod::Schema(value)
+            | SelectionMethod::Tag(value) => value.is_empty(),
+            SelectionMethod::Path(path) => path.as_os_str().is_empty(),
+        };
+        if value_is_empty {
+            return Err(invalid("nothing to match"));
+        }
+
+        Ok(Self {
+            method,
+            upstream,
+            downstream,
+        })
+    }
+
+    fn select(&self, catalog: &ModelCatalog) -> Result<BTreeSet<String>> {
+        let matched: BTreeSet<String> = match &self.method {
+            SelectionMethod::Model(name) => {
+                if !catalog.models.contains_key(name) {
+                    return Err(eyre!("No model named '{}'", name));
+                }
+                BTreeSet::from([name.clone()])
+            }
+            SelectionMethod::Schema(schema) => catalog
+                .model_identities
+                .iter()
+                .filter(|(_, identity)| &identity.schema_name == schema)
+                .map(|(name, _)| name.clone())
+                .collect(),
+            SelectionMethod::Tag(tag) => catalog
+                .models
+                .keys()
+                .filter(|name| catalog.get_tags(name).contains(tag))
+                .cloned()
+                .collect(),
+            SelectionMethod::Path(path) => {
+                // Paths may be given from the project root or the model directory
+                let from_root = normalize(path);
+                let from_model_dir = normalize(&catalog.model_dir.join(path));
+                catalog
+                    .model_identities
+                    .iter()
+                    .filter(|(_, identity)| {
+                        let file_path = normalize(&identity.file_path);
+                        file_path.starts_with(&from_root) || file_path.starts_with(&from_model_dir)
+                    })
+                    .map(|(name, _)| name.clone())
+                    .collect()
+            }
+        };
+
+        let mut selected = matched.clone();
+        for model in &matched {
+            if let Some(depth) = self.upstream {
+                selected.extend(catalog.dependency_graph.get_upstream(model, depth.limit()));
+            }
+            if let Some(depth) = self.downstream {
+                selected.extend(
+                    catalog
+                        .dependency_graph
+                        .get_downstream(model, depth.limit()),
+                );
+            }
+        }
+        Ok(selected)
+    }
+}
+
+/// Select models from the catalog with `--select` and `--exclude` selectors.
+/// Every model is selected when no `--select` selectors are given.
+pub fn select_models(
+    catalog: &ModelCatalog,
+    select: &[String],
+    exclude: &[String],
+) -> Result<BTreeSet<String>> {
+    let mut selected = if select.is_empty() {
+        catalog.models.keys().cloned().collect()
+    } else {
+        Selector::parse(&select.join(" "))?.select(catalog)?
+    };
+
+    if !exclude.is_empty() {
+        let excluded = Selector::parse(&exclude.join(" "))?.select(catalog)?;
+        selected.retain(|model| !excluded.contains(model));
+    }
+    Ok(selected)
+}
+
+/// Parse the digits before or after a `+` in a selector, where no digits
+/// means no limit
+fn parse_depth(selector: &str, digits: &str) -> Result<Depth> {
+    if digits.is_empty() {
+        return Ok(Depth::Unlimited);
+    }
+    digits.parse().map(Depth::Limited).map_err(|_| {
+        eyre!(
+            "Invalid selector '{}': depth {} is too large",
+            selector,
+            digits
+        )
+    })
+}
+
+/// Drop `.` components so `./models/gold` matches `models/gold`
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| *component != Component::CurDir)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ProjectConfig;
+    use crate::parser::ModelMetadata;
+
+    /// Catalog of bronze.users <- silver.customers <- gold.orders <- gold.revenue,
+    /// plus an unrelated bronze.events, without reading any files
+    fn catalog() -> ModelCatalog {
+        let models = [
+            ("bronze.users", vec![], vec![]),
+            ("bronze.events", vec![], vec![]),
+            ("silver.customers", vec!["bronze.users"], vec!["pii"]),
+            ("gold.orders", vec!["silver.customers"], vec!["finance"]),
+            (
+                "gold.revenue",
+                vec!["gold.orders"],
+                vec!["finance", "daily"],
+            ),
+        ]
+        .into_iter()
+        .map(|(name, source_ids, tags)| {
+            let mut model = ModelMetadata::selecting_from(name, &source_ids);
+            model.config.tags = tags.into_iter().map(str::to_string).collect();
+            model
+        });
+        let mut catalog = ModelCatalog::from_models(ProjectConfig::default(), models).unwrap();
+        catalog.build_dependency_graph().unwrap();
+        catalog
+    }
+
+    fn select(selector: &str) -> Vec<String> {
+        Selector::parse(selector)
+            .and_then(|selector| selector.select(&catalog()))
+            .unwrap()
+            .into_iter()
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_criterion() -> Result<()> {
+        let criterion = Criterion::parse("+gold.orders")?;
+        assert_eq!(
+            criterion.method,
+            SelectionMethod::Model("gold.orders".to_string())
+        );
+        assert_eq!(criterion.upstream, Some(Depth::Unlimited));
+        assert_eq!(criterion.downstream, None);
+
+        let criterion = Criterion::parse("2+schema:bronze+1")?;
+        assert_eq!(
+            criterion.method,
+            SelectionMethod::Schema("bronze".to_string())
+        );
+        assert_eq!(criterion.upstream, Some(Depth::Limited(2)));
+        assert_eq!(criterion.downstream, Some(Depth::Limited(1)));
+
+        // Digits are only a depth when followed by `+`
+        let criterion = Criterion::parse("raw.2024_events")?;
+        assert_eq!(
+            criterion.method,
+            SelectionMethod::Model("raw.2024_events".to_string())
+        );
+
+        assert!(Criterion::parse("owner:jan").is_err());
+        assert!(Criterion::parse("tag:").is_err());
+        assert!(Criterion::parse("+").is_err());
+
+        let error = Criterion::parse("gold.orders+99999999999999999999").unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("depth 99999999999999999999 is too large")
+        );
+        assert!(Criterion::parse("99999999999999999999+gold.orders").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_by_method() {
+        assert_eq!(select("gold.orders"), vec!["gold.orders"]);
+        assert_eq!(
+            select("schema:bronze"),
+            vec!["bronze.events", "bronze.users"]
+        );
+        assert_eq!(select("tag:finance"), vec!["gold.orders", "gold.revenue"]);
+        assert_eq!(select("path:models/silver"), vec!["silver.customers"]);
+        assert_eq!(
+            select("path:./models/gold/revenue.sql"),
+            vec!["gold.revenue"]
+        );
+        // Paths relative to the model directory work too
+        assert_eq!(select("path:gold"), vec!["gold.orders", "gold.revenue"]);
+
+        assert!(
+            Selector::parse("gold.missing")
+                .unwrap()
+                .select(&catalog())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_select_through_graph() {
+        assert_eq!(
+            select("+gold.orders"),
+            vec!["bronze.users", "gold.orders", "silver.customers"]
+        );
+        assert_eq!(
+            select("1+gold.orders"),
+            vec!["gold.orders", "silver.customers"]
+        );
+        assert_eq!(
+            select("bronze.users+"),
+            vec![
+                "bronze.users",
+                "gold.orders",
+                "gold.revenue",
+                "silver.customers"
+            ]
+        );
+        assert_eq!(
+            select("bronze.users+1"),
+            vec!["bronze.users", "silver.customers"]
+        );
+    }
+
+    #[test]
+    fn test_select_unions_and_intersections() {
+        // Spaces union, commas intersect
+        assert_eq!(
+            select("tag:pii schema:bronze"),
+            vec!["bronze.events", "bronze.users", "silver.customers"]
+        );
+        assert_eq!(select("tag:finance,tag:daily"), vec!["gold.revenue"]);
+        assert_eq!(select("+gold.orders,schema:bronze"), vec!["bronze.users"]);
+    }
+
+    #[test]
+    fn test_select_models_with_exclude() -> Result<()> {
+        let catalog = catalog();
+
+        let all = select_models(&catalog, &[], &[])?;
+        assert_eq!(all.len(), 5);
+
+        let selected = select_models(
+            &catalog,
+            &["bronze.users+".to_string(), "bronze.events".to_string()],
+            &["tag:finance".to_string()],
+        )?;
+        let selected: Vec<&str> = selected.iter().map(String::as_str).collect();
+        assert_eq!(
+            selected,
+            vec!["bronze.events", "bronze.users", "silver.customers"]
+        );
+
+        Ok(())
+    }
+}